@@ -0,0 +1,135 @@
+use crate::export::{self, HtmlRenderer};
+use crate::types::{ArchiveSummary, ChatMessage, ChatSession, CommandError, ExportFormat, UsageRange};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const MANIFEST_NAME: &str = "manifest.json";
+
+// Written at the root of the archive, describes what was selected and where
+// each session's files are
+#[derive(Serialize)]
+struct ArchiveManifest<'a> {
+    created_at: String,
+    project_path: Option<&'a str>,
+    range: &'a UsageRange,
+    format: ExportFormat,
+    sessions: Vec<ArchivedSession>,
+}
+
+#[derive(Serialize)]
+struct ArchivedSession {
+    #[serde(flatten)]
+    session: ChatSession,
+    jsonl_file: String,
+    rendered_file: String,
+}
+
+/// Writes sessions into a zip archive, each one as its original JSONL file
+/// next to a rendered copy, grouped by project directory.
+pub struct ArchiveWriter {
+    zip: ZipWriter<std::fs::File>,
+    destination: PathBuf,
+    partial_path: PathBuf,
+    format: ExportFormat,
+    html: Option<HtmlRenderer>,
+    sessions: Vec<ArchivedSession>,
+    entry_names: HashSet<String>,
+}
+
+impl ArchiveWriter {
+    pub fn create(destination: &Path, format: ExportFormat) -> Result<Self> {
+        // Written under a temporary name so a failed export never leaves a
+        // truncated archive where the user expects a complete one
+        let mut partial_name = destination.file_name().context("Archive path has no file name")?.to_os_string();
+        partial_name.push(".part");
+        let partial_path = destination.with_file_name(partial_name);
+
+        let file = std::fs::File::create(&partial_path).map_err(|e| CommandError::io(e, &partial_path))?;
+
+        Ok(Self {
+            zip: ZipWriter::new(file),
+            destination: destination.to_path_buf(),
+            partial_path,
+            format,
+            html: (format == ExportFormat::Html).then(HtmlRenderer::new),
+            sessions: Vec::new(),
+            entry_names: HashSet::new(),
+        })
+    }
+
+    pub fn add_session(
+        &mut self,
+        folder: &str,
+        file_name: &str,
+        session: &ChatSession,
+        raw: &[u8],
+        messages: &[ChatMessage],
+    ) -> Result<()> {
+        let stem = file_name.strip_suffix(".jsonl").unwrap_or(file_name);
+        let (rendered, extension) = match &self.html {
+            Some(renderer) => (renderer.render(session, messages), "html"),
+            None => (export::to_markdown(session, messages), "md"),
+        };
+
+        let jsonl_file = format!("{}/{}", folder, file_name);
+        let rendered_file = format!("{}/{}.{}", folder, stem, extension);
+        // Equally named project folders of different roots share a folder in the
+        // archive, where a copy of a session already written is skipped
+        if self.entry_names.contains(&jsonl_file) {
+            return Ok(());
+        }
+        self.write_entry(&jsonl_file, raw)?;
+        self.write_entry(&rendered_file, rendered.as_bytes())?;
+
+        self.sessions.push(ArchivedSession {
+            session: session.clone(),
+            jsonl_file,
+            rendered_file,
+        });
+        Ok(())
+    }
+
+    fn write_entry(&mut self, name: &str, contents: &[u8]) -> Result<()> {
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(contents.len() as u64 >= u32::MAX as u64);
+        self.zip.start_file(name, options)?;
+        self.entry_names.insert(name.to_string());
+        self.zip.write_all(contents)?;
+        Ok(())
+    }
+
+    /// Writes the manifest and moves the archive to its destination.
+    pub fn finish(mut self, project_path: Option<&str>, range: &UsageRange) -> Result<ArchiveSummary> {
+        let sessions = std::mem::take(&mut self.sessions);
+        let session_count = sessions.len();
+        let manifest = ArchiveManifest {
+            created_at: chrono::Utc::now().to_rfc3339(),
+            project_path,
+            range,
+            format: self.format,
+            sessions,
+        };
+        self.write_entry(MANIFEST_NAME, &serde_json::to_vec_pretty(&manifest)?)?;
+
+        self.zip.finish()?.sync_all()?;
+        std::fs::rename(&self.partial_path, &self.destination)?;
+
+        Ok(ArchiveSummary {
+            path: self.destination.to_string_lossy().to_string(),
+            sessions: session_count,
+            bytes: std::fs::metadata(&self.destination)?.len(),
+        })
+    }
+
+    /// Deletes the unfinished archive after a failed export.
+    pub fn abort(self) {
+        drop(self.zip);
+        std::fs::remove_file(&self.partial_path).ok();
+    }
+}
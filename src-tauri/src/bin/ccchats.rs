@@ -0,0 +1,240 @@
+// Command line access to the chat history, for machines without a display
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use claude_code_chats_lib::chat_service::ChatService;
+use claude_code_chats_lib::types::{ChatSession, ExportFormat, ProjectFolder, SearchMode, SessionFile};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+// Same directory the GUI keeps its caches in, so both share one session index
+const APP_IDENTIFIER: &str = "com.claude-code-chats.app";
+
+#[derive(Parser)]
+#[command(name = "ccchats", version, about = "Browse, search and export Claude Code chat history")]
+struct Cli {
+    /// Print JSON instead of human-readable output
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Claude config or projects directory to read instead of the configured
+    /// ones, can be given several times
+    #[arg(long = "root", value_name = "DIR", global = true)]
+    roots: Vec<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List projects, most recently active first
+    ListProjects,
+    /// List the sessions of a project, most recently active first
+    ListSessions {
+        /// Project name, directory name or directory path, as shown by list-projects
+        #[arg(allow_hyphen_values = true)]
+        project: String,
+    },
+    /// Print the messages of a session
+    Show {
+        session: String,
+    },
+    /// Search the messages of every session
+    Search {
+        query: String,
+        /// text, regex or fuzzy
+        #[arg(long, default_value = "text", value_parser = parse_lowercase::<SearchMode>)]
+        mode: SearchMode,
+    },
+    /// List the files a session read, edited, wrote or created
+    Files {
+        session: String,
+    },
+    /// List the sessions that read or changed a file, most recent first
+    History {
+        path: PathBuf,
+    },
+    /// Render a session as a standalone document
+    Export {
+        session: String,
+        /// markdown or html
+        #[arg(long, default_value = "markdown", value_parser = parse_lowercase::<ExportFormat>)]
+        format: ExportFormat,
+        /// File to write to instead of standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+// Option values are spelled the way the frontend sends them
+fn parse_lowercase<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_lowercase())).map_err(|e| e.to_string())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let data_dir = dirs::data_dir().map(|data_dir| data_dir.join(APP_IDENTIFIER));
+    let service = match (data_dir, cli.roots.is_empty()) {
+        (Some(data_dir), true) => ChatService::with_data_dir(&data_dir),
+        (None, true) => ChatService::new(),
+        // The app's indexes cover its own roots, refreshing them against other roots
+        // would drop every session outside those
+        (Some(data_dir), false) => ChatService::with_prices_from(&data_dir).with_roots(cli.roots.clone()),
+        (None, false) => ChatService::new().with_roots(cli.roots.clone()),
+    };
+
+    // Output is printed in one go so that a closed pipe (e.g. `| head`) ends the
+    // program quietly instead of panicking in println!
+    match run(&service, cli).await {
+        Ok(output) => {
+            std::io::stdout().write_all(output.as_bytes()).ok();
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(service: &ChatService, cli: Cli) -> Result<String> {
+    let mut out = String::new();
+    match cli.command {
+        Command::ListProjects => {
+            let projects = service.get_all_projects().await?;
+            if cli.json {
+                return to_json(&projects);
+            }
+            for project in &projects {
+                let last_updated = project.chat_sessions.iter().map(|session| session.last_updated.as_str()).max();
+                writeln!(
+                    out,
+                    "{:<16}  {:>5} sessions  {:>9}  {}",
+                    last_updated.map(format_timestamp).unwrap_or_default(),
+                    project.chat_sessions.len(),
+                    format!("${:.2}", project.cost_usd),
+                    project.name
+                )?;
+            }
+        }
+        Command::ListSessions { project } => {
+            let project = find_project(service, &project).await?;
+            if cli.json {
+                return to_json(&project.chat_sessions);
+            }
+            for session in &project.chat_sessions {
+                write_session(&mut out, session)?;
+            }
+        }
+        Command::Show { session } => {
+            if cli.json {
+                return to_json(&service.get_chat_messages(&session).await?);
+            }
+            out = service.export_session(&session, ExportFormat::Markdown).await?;
+        }
+        Command::Search { query, mode } => {
+            let results = service.search_chats(&query, mode).await?;
+            if cli.json {
+                return to_json(&results);
+            }
+            for result in &results {
+                writeln!(
+                    out,
+                    "{}  {}  {:<9}  {}",
+                    format_timestamp(&result.timestamp),
+                    result.session_id,
+                    result.role,
+                    result.session_title.as_deref().unwrap_or("Untitled")
+                )?;
+                for search_match in &result.matches {
+                    let label = search_match.tool_name.as_deref().unwrap_or(&search_match.match_type);
+                    writeln!(out, "    [{}] {}", label, search_match.snippet.replace('\n', " "))?;
+                }
+            }
+        }
+        Command::Files { session } => {
+            let files = service.get_session_files(&session).await?;
+            if cli.json {
+                return to_json(&files);
+            }
+            for file in &files {
+                write_file_activity(&mut out, file)?;
+                writeln!(out, "  {}", file.path)?;
+            }
+        }
+        Command::History { path } => {
+            // Sessions record absolute paths, so a relative argument is taken from here
+            let path = std::path::absolute(&path)?;
+            let entries = service.get_file_history(&path).await?;
+            if cli.json {
+                return to_json(&entries);
+            }
+            for entry in &entries {
+                write!(out, "{:<16}  {}  ", format_timestamp(&entry.file.last_touched), entry.session.id)?;
+                write_file_activity(&mut out, &entry.file)?;
+                writeln!(out, "  {}", entry.session.title)?;
+            }
+        }
+        Command::Export { session, format, output } => {
+            let document = service.export_session(&session, format).await?;
+            match output {
+                Some(path) => tokio::fs::write(&path, document).await?,
+                None => out = document,
+            }
+        }
+    }
+    Ok(out)
+}
+
+async fn find_project(service: &ChatService, project: &str) -> Result<ProjectFolder> {
+    service
+        .get_all_projects()
+        .await?
+        .into_iter()
+        .find(|folder| {
+            let path = std::path::Path::new(&folder.path);
+            folder.name == project || path == std::path::Path::new(project) || path.file_name().is_some_and(|name| name == project)
+        })
+        .ok_or_else(|| anyhow!("No project named {}", project))
+}
+
+fn write_session(out: &mut String, session: &ChatSession) -> std::fmt::Result {
+    writeln!(
+        out,
+        "{:<16}  {}  {:>4} messages  {:>9}  {}",
+        format_timestamp(&session.last_updated),
+        session.id,
+        session.message_count,
+        format!("${:.2}", session.cost_usd),
+        session.title
+    )
+}
+
+// Reads, edits, writes and globs like "2r 1e 0w 0g", with a "+" when the session created the file
+fn write_file_activity(out: &mut String, file: &SessionFile) -> std::fmt::Result {
+    write!(
+        out,
+        "{:>3}r {:>3}e {:>3}w {:>3}g{}",
+        file.reads,
+        file.edits,
+        file.writes,
+        file.globs,
+        if file.created { "+" } else { " " }
+    )
+}
+
+fn to_json(value: &impl Serialize) -> Result<String> {
+    Ok(serde_json::to_string_pretty(value)? + "\n")
+}
+
+fn format_timestamp(timestamp: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|date| date.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
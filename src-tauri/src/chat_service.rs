@@ -1,46 +1,143 @@
+use crate::archive::ArchiveWriter;
+use crate::conversation_tree::ConversationGraph;
+use crate::data_roots::{default_root, DataRoots};
+use crate::export;
+use crate::file_activity::FileActivityCollector;
+use crate::image_cache::ImageCache;
+use crate::pricing::{PriceTable, Pricing};
+use crate::search_index::{SearchDocument, SearchIndex};
+use crate::search_query::SearchMatcher;
+use crate::session_index::{FileStamp, IndexedSessionFile, SessionIndex};
+use crate::tool_diff::file_diff;
+use crate::tool_stats::ToolStatsCollector;
 use crate::types::*;
+use crate::usage_stats::{local_date, ActivityTracker, UsageRollup};
 use anyhow::{Context, Result};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 use tokio::fs;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use std::io::SeekFrom;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, BufReader, Split};
 
 pub struct ChatService {
-    projects_path: PathBuf,
+    data_roots: DataRoots,
+    session_index: Option<SessionIndex>,
+    search_index: Option<SearchIndex>,
+    pricing: Pricing,
+    images: ImageCache,
+    // Session titles taken when the current search started, reused by its later pages
+    search_titles: RwLock<Arc<HashMap<String, String>>>,
+    // Id of the streaming search that is allowed to keep running, 0 when none is
+    active_search: AtomicU64,
+}
+
+// Upper bound on hits returned by search_chats, use search_chats_page for more
+const MAX_RESULTS: usize = 500;
+// Number of results per event emitted by a streaming search
+const STREAM_BATCH_SIZE: usize = 50;
+// Skipped lines recorded per file, the rest are only counted
+const MAX_PARSE_ISSUES: usize = 50;
+
+impl Default for ChatService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ChatService {
     pub fn new() -> Self {
-        let projects_path = dirs::home_dir()
-            .expect("Unable to determine home directory")
-            .join(".claude")
-            .join("projects");
-        Self { projects_path }
+        Self {
+            data_roots: DataRoots::new(default_root().into_iter().collect()),
+            session_index: None,
+            search_index: None,
+            pricing: Pricing::new(),
+            images: ImageCache::new(None),
+            search_titles: RwLock::new(Arc::new(HashMap::new())),
+            active_search: AtomicU64::new(0),
+        }
+    }
+
+    /// Reads sessions from `roots` instead of the configured data roots.
+    pub fn with_roots(self, roots: Vec<PathBuf>) -> Self {
+        Self {
+            data_roots: DataRoots::new(roots),
+            ..self
+        }
+    }
+
+    /// Keeps images decoded from sessions in `dir` so they can be served by `read_image`.
+    pub fn with_image_cache(self, dir: PathBuf) -> Self {
+        Self {
+            images: ImageCache::new(Some(dir)),
+            ..self
+        }
+    }
+
+    /// The projects directories of all data roots that currently exist.
+    pub fn projects_dirs(&self) -> Vec<PathBuf> {
+        self.data_roots.projects_dirs()
+    }
+
+    /// Creates a service that keeps its session cache and full-text search index
+    /// in `data_dir`, so unchanged JSONL files are not re-parsed on every call.
+    pub fn with_data_dir(data_dir: &Path) -> Self {
+        Self {
+            session_index: Some(SessionIndex::load(data_dir.join("session_index.json"))),
+            // Searching falls back to scanning the JSONL files if the index can't be
+            // opened, e.g. because another instance holds its writer lock
+            search_index: SearchIndex::open(&data_dir.join("search_index")).ok(),
+            pricing: Pricing::load(data_dir.join("pricing.json")),
+            data_roots: DataRoots::load(data_dir.join("data_roots.json")),
+            ..Self::new()
+        }
+    }
+
+    /// Takes only the price table from `data_dir`, without the session cache and
+    /// search index, which cover the configured data roots and nothing else.
+    pub fn with_prices_from(data_dir: &Path) -> Self {
+        Self {
+            pricing: Pricing::load(data_dir.join("pricing.json")),
+            ..Self::new()
+        }
+    }
+
+    /// A cached image and its media type, for the image protocol.
+    pub fn read_image(&self, id: &str) -> Option<(Vec<u8>, &'static str)> {
+        self.images.load(id)
     }
 
     pub async fn get_all_projects(&self) -> Result<Vec<ProjectFolder>> {
         let mut projects = Vec::new();
-        let mut entries = fs::read_dir(&self.projects_path).await?;
 
-        while let Some(entry) = entries.next_entry().await? {
-            if entry.file_type().await?.is_dir() {
-                let project_path = entry.path();
-                
-                let sessions = self.get_project_sessions(&project_path).await?;
-                
-                if !sessions.is_empty() {
-                    // Use the real project path from the first session's cwd property
-                    let project_name = sessions[0].project_path.clone();
-                    
-                    projects.push(ProjectFolder {
-                        name: project_name,
-                        path: project_path.to_string_lossy().to_string(),
-                        chat_sessions: sessions,
-                    });
-                }
+        for (project_path, session_files) in self.project_session_files(None).await? {
+            let sessions = self.sessions_in(&session_files).await?;
+
+            if !sessions.is_empty() {
+                // Use the real project path from the first session's cwd property
+                let project_name = sessions[0].project_path.clone();
+                let mut usage = TokenUsage::default();
+                sessions.iter().for_each(|session| usage += session.usage);
+                let cost_usd = sessions.iter().map(|session| session.cost_usd).sum();
+
+                projects.push(ProjectFolder {
+                    name: project_name,
+                    path: project_path.to_string_lossy().to_string(),
+                    chat_sessions: sessions,
+                    usage,
+                    cost_usd,
+                });
             }
         }
 
+        if let Some(index) = &self.session_index {
+            index.prune_missing();
+            // The index is only a cache, so failing to persist it must not fail the listing
+            index.save().await.ok();
+        }
+
         // Sort projects by most recent activity
         projects.sort_by(|a, b| {
             let empty_string = String::new();
@@ -59,61 +156,192 @@ impl ChatService {
     }
 
     pub async fn get_project_sessions(&self, project_path: &Path) -> Result<Vec<ChatSession>> {
-        let mut sessions = Vec::new();
-        
-        // Build summary index upfront for this project
-        let summary_index = self.build_summary_index(project_path).await;
-        
-        let mut entries = fs::read_dir(project_path).await?;
+        let session_files = self.session_files_in(project_path).await?;
+        self.sessions_in(&session_files).await
+    }
 
-        while let Some(entry) = entries.next_entry().await? {
-            if entry.file_type().await?.is_file() {
-                let file_path = entry.path();
-                if file_path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
-                    if let Ok(session) = self.parse_session_from_file_with_index(&file_path, &summary_index).await {
-                        sessions.push(session);
-                    }
+    // The sessions of some files of one project, most recently active first
+    async fn sessions_in(&self, session_files: &[PathBuf]) -> Result<Vec<ChatSession>> {
+        let indexed_files = self.load_session_files(session_files).await?;
+
+        // Summaries can live in a different file than the session they describe,
+        // so resolve titles against every summary in the project
+        let summary_index: HashMap<&str, &str> = indexed_files
+            .iter()
+            .flat_map(|indexed| indexed.summaries.iter())
+            .map(|(leaf_uuid, summary)| (leaf_uuid.as_str(), summary.as_str()))
+            .collect();
+
+        let mut sessions: Vec<ChatSession> = indexed_files
+            .iter()
+            .filter_map(|indexed| {
+                let mut session = indexed.session.clone()?;
+                if let Some(summary) = summary_index.get(indexed.last_message_uuid.as_str()) {
+                    session.title = summary.to_string();
                 }
-            }
-        }
+                // Costs aren't cached so that price table changes apply immediately
+                for record in &indexed.activity {
+                    session.usage += record.usage;
+                    session.cost_usd += self.pricing.cost(&record.model, &record.usage);
+                }
+                Some(session)
+            })
+            .collect();
 
         // Sort by most recent first
         sessions.sort_by(|a, b| b.last_updated.cmp(&a.last_updated));
         Ok(sessions)
     }
 
+    async fn load_project_files(&self, project_path: &Path) -> Result<Vec<IndexedSessionFile>> {
+        let session_files = self.session_files_in(project_path).await?;
+        self.load_session_files(&session_files).await
+    }
+
+    async fn load_session_files(&self, session_files: &[PathBuf]) -> Result<Vec<IndexedSessionFile>> {
+        let mut indexed_files = Vec::new();
+        for file_path in session_files {
+            let stamp = FileStamp::from_metadata(&fs::metadata(file_path).await?);
+            if let Some(indexed) = self.load_session_file(file_path, stamp).await {
+                indexed_files.push(indexed);
+            }
+        }
+        Ok(indexed_files)
+    }
+
+    async fn load_session_file(&self, file_path: &Path, stamp: FileStamp) -> Option<IndexedSessionFile> {
+        if let Some(index) = &self.session_index {
+            if let Some(indexed) = index.get(file_path, stamp) {
+                return Some(indexed);
+            }
+        }
+
+        let indexed = self.parse_session_file(file_path, stamp).await.ok()?;
+        if let Some(index) = &self.session_index {
+            index.insert(file_path, indexed.clone());
+        }
+        Some(indexed)
+    }
+
     pub async fn get_chat_messages(&self, session_id: &str) -> Result<Vec<ChatMessage>> {
         // Find the JSONL file for this session
         let jsonl_path = self.find_session_file(session_id).await?;
-        self.parse_messages_from_file(&jsonl_path).await
+        self.parse_messages_from_file(&jsonl_path, true).await
+    }
+
+    /// The listing entry of a single session, with its title resolved.
+    pub async fn get_session(&self, session_id: &str) -> Result<ChatSession> {
+        let jsonl_path = self.find_session_file(session_id).await?;
+        let project_dir = jsonl_path.parent().context("Session file has no parent directory")?;
+        self.get_project_sessions(project_dir)
+            .await?
+            .into_iter()
+            .find(|session| session.id == session_id)
+            .ok_or_else(|| {
+                let message = format!("Session {} has no messages", session_id);
+                CommandError::new(CommandErrorKind::SessionNotFound, message).with_path(&jsonl_path).into()
+            })
+    }
+
+    /// Renders a session as a standalone document in `format`.
+    pub async fn export_session(&self, session_id: &str, format: ExportFormat) -> Result<String> {
+        let session = self.get_session(session_id).await?;
+        let messages = self.get_chat_messages(session_id).await?;
+        Ok(match format {
+            ExportFormat::Markdown => export::to_markdown(&session, &messages),
+            ExportFormat::Html => export::HtmlRenderer::new().render(&session, &messages),
+        })
+    }
+
+    /// Writes the sessions selected by `request` to a zip archive at `destination`,
+    /// with their raw JSONL, a rendered copy and a manifest of their metadata.
+    pub async fn export_archive(&self, request: ArchiveRequest, destination: &Path) -> Result<ArchiveSummary> {
+        request.range.validate()?;
+
+        let project_files = self.project_session_files(request.project_path.as_deref().map(Path::new)).await?;
+
+        // Select everything up front so that nothing is written when no session matches
+        let mut selected = Vec::new();
+        for (project_dir, session_files) in project_files {
+            let sessions: HashMap<String, ChatSession> = self
+                .sessions_in(&session_files)
+                .await?
+                .into_iter()
+                .map(|session| (session.id.clone(), session))
+                .collect();
+
+            for file_path in session_files {
+                let stamp = FileStamp::from_metadata(&fs::metadata(&file_path).await?);
+                let Some(session) = self
+                    .load_session_file(&file_path, stamp)
+                    .await
+                    .and_then(|indexed| indexed.session)
+                    .and_then(|session| sessions.get(&session.id).cloned())
+                else {
+                    continue;
+                };
+                if request.range.overlaps(&local_date(&session.timestamp), &local_date(&session.last_updated)) {
+                    selected.push((project_dir.clone(), file_path, session));
+                }
+            }
+        }
+
+        if selected.is_empty() {
+            return Err(CommandError::new(CommandErrorKind::InvalidInput, "No sessions match the export selection").into());
+        }
+
+        let mut archive = ArchiveWriter::create(destination, request.format)?;
+        for (project_dir, file_path, session) in &selected {
+            let folder = project_dir.file_name().unwrap_or_default().to_string_lossy();
+            let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
+
+            let added: Result<()> = async {
+                let raw = fs::read(file_path).await?;
+                let messages = self.parse_messages_from_file(file_path, true).await?;
+                archive.add_session(&folder, &file_name, session, &raw, &messages)
+            }
+            .await;
+            if let Err(e) = added {
+                archive.abort();
+                return Err(e.context(format!("Failed to archive {}", file_path.display())));
+            }
+        }
+
+        archive.finish(request.project_path.as_deref(), &request.range)
     }
 
     async fn find_session_file(&self, session_id: &str) -> Result<PathBuf> {
-        let mut entries = fs::read_dir(&self.projects_path).await?;
+        if let Some(index) = &self.session_index {
+            if let Some(file_path) = index.find_session_file(session_id) {
+                if file_path.exists() {
+                    return Ok(file_path);
+                }
+            }
+        }
 
-        while let Some(project_entry) = entries.next_entry().await? {
-            if project_entry.file_type().await?.is_dir() {
-                let mut project_files = fs::read_dir(project_entry.path()).await?;
-                
-                while let Some(file_entry) = project_files.next_entry().await? {
-                    if file_entry.file_type().await?.is_file() {
-                        let file_path = file_entry.path();
-                        if file_path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
-                            // Check if this file contains our session by reading through lines
-                            if self.file_contains_session_id(&file_path, session_id).await? {
-                                return Ok(file_path);
-                            }
+        for project_dir in self.project_dirs().await? {
+            let mut project_files = fs::read_dir(&project_dir).await?;
+
+            while let Some(file_entry) = project_files.next_entry().await? {
+                if file_entry.file_type().await?.is_file() {
+                    let file_path = file_entry.path();
+                    if file_path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
+                        // Check if this file contains our session by reading through lines. A
+                        // file that can't be read must not hide the sessions in the other files
+                        if self.file_contains_session_id(&file_path, session_id).await.unwrap_or(false) {
+                            return Ok(file_path);
                         }
                     }
                 }
             }
         }
 
-        Err(anyhow::anyhow!("Session file not found for ID: {}", session_id))
+        let message = format!("Session file not found for ID: {}", session_id);
+        Err(CommandError::new(CommandErrorKind::SessionNotFound, message).into())
     }
 
     async fn file_contains_session_id(&self, file_path: &Path, session_id: &str) -> Result<bool> {
-        let file = fs::File::open(file_path).await?;
+        let file = fs::File::open(file_path).await.map_err(|e| CommandError::io(e, file_path))?;
         let reader = BufReader::new(file);
         let mut lines = reader.lines();
         
@@ -141,145 +369,445 @@ impl ChatService {
     }
 
 
-    async fn parse_session_from_file_with_index(&self, file_path: &Path, summary_index: &HashMap<String, String>) -> Result<ChatSession> {
-        let file = fs::File::open(file_path).await?;
+    async fn parse_session_file(&self, file_path: &Path, stamp: FileStamp) -> Result<IndexedSessionFile> {
+        let file = fs::File::open(file_path).await.map_err(|e| CommandError::io(e, file_path))?;
         let reader = BufReader::new(file);
-        let mut lines = reader.lines();
+        let mut lines = reader.split(b'\n');
         
         let mut session_id = String::new();
         let mut project_path = String::new();
         let mut first_message: Option<ChatMessage> = None;
+        // First prompt the user typed, a better title than a command or injected context
+        let mut title_message: Option<ChatMessage> = None;
         let mut message_count = 0;
         let mut last_updated = String::new();
         let mut last_message_uuid = String::new();
+        let mut summaries = HashMap::new();
+        let mut activity = ActivityTracker::default();
+        let mut issues = Vec::new();
+        let mut issue_count = 0;
+        let mut line_number = 0;
 
-        while let Some(line) = lines.next_line().await? {
+        while let Some(line) = self.next_line(&mut lines, file_path, &mut line_number).await? {
             if line.trim().is_empty() {
                 continue;
             }
 
-            // First check if this is a summary object - if so, skip it
-            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&line) {
-                if let Some(line_type) = json_value.get("type").and_then(|v| v.as_str()) {
-                    if line_type == "summary" {
-                        continue; // Skip summary objects
-                    }
+            // A bad line is skipped and reported rather than hiding the whole session
+            let mut report = |error: String| {
+                issue_count += 1;
+                if issues.len() < MAX_PARSE_ISSUES {
+                    issues.push(ParseIssue::new(line_number, error, &line));
+                }
+            };
+
+            let json_value = match serde_json::from_str::<serde_json::Value>(&line) {
+                Ok(json_value) => json_value,
+                Err(e) => {
+                    report(format!("Invalid JSON: {}", e));
+                    continue;
                 }
+            };
+            let line_type = json_value.get("type").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+
+            // Collect summary objects for title lookup instead of treating them as messages
+            if line_type == "summary" {
+                if let (Some(leaf_uuid), Some(summary)) = (
+                    json_value.get("leafUuid").and_then(|v| v.as_str()),
+                    json_value.get("summary").and_then(|v| v.as_str())
+                ) {
+                    summaries.insert(leaf_uuid.to_string(), summary.to_string());
+                }
+                continue;
             }
 
-            let raw_msg: RawJsonlMessage = serde_json::from_str(&line)
-                .context("Failed to parse JSONL line")?;
+            let is_message = line_type == "user" || line_type == "assistant";
+            let raw_msg: RawJsonlMessage = match serde_json::from_value(json_value) {
+                Ok(raw_msg) => raw_msg,
+                // Other line types, e.g. from newer Claude Code versions, aren't needed
+                Err(e) if is_message => {
+                    report(format!("Unexpected {} message format: {}", line_type, e));
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            if is_message && raw_msg.message.content.is_null() {
+                report(format!("{} message without content", line_type));
+                continue;
+            }
 
             if session_id.is_empty() {
                 session_id = raw_msg.session_id.clone();
+            }
+            if project_path.is_empty() {
                 project_path = raw_msg.cwd.clone();
             }
 
-            if raw_msg.message_type == "user" || raw_msg.message_type == "assistant" {
+            if is_message {
+                if title_message.is_none() && raw_msg.message_type == "user" {
+                    match self.convert_raw_to_chat_message(&raw_msg, false) {
+                        Ok(chat_msg) => {
+                            if chat_msg.kind == MessageKind::Conversation {
+                                title_message = Some(chat_msg.clone());
+                            }
+                            first_message.get_or_insert(chat_msg);
+                        }
+                        Err(e) => {
+                            report(format!("Failed to convert message: {}", e));
+                            continue;
+                        }
+                    }
+                }
+
                 message_count += 1;
                 last_updated = raw_msg.timestamp.clone();
                 last_message_uuid = raw_msg.uuid.clone();
 
-                if first_message.is_none() && raw_msg.message_type == "user" {
-                    let chat_msg = self.convert_raw_to_chat_message(&raw_msg)?;
-                    first_message = Some(chat_msg);
-                }
+                activity.add(&raw_msg);
             }
         }
 
-        if let Some(first_msg) = first_message {
-            // Look up summary from index (much faster than file scanning)
-            let summary_title = summary_index.get(&last_message_uuid).cloned();
-            
-            let mut session = ChatSession::new_with_summary(session_id, &first_msg, project_path, summary_title);
+        let session = first_message.map(|first_msg| {
+            // The title is resolved against the project's summaries by the caller
+            let title_msg = title_message.as_ref().unwrap_or(&first_msg);
+            let mut session = ChatSession::new_with_summary(session_id, title_msg, project_path, None);
+            session.timestamp = first_msg.timestamp.clone();
             session.message_count = message_count;
             session.last_updated = last_updated;
-            Ok(session)
-        } else {
-            Err(anyhow::anyhow!("No valid messages found in file"))
+            session
+        });
+
+        Ok(IndexedSessionFile {
+            stamp,
+            session,
+            last_message_uuid,
+            summaries,
+            activity: activity.finish(),
+            issues,
+            issue_count,
+        })
+    }
+
+    // The messages of a session file as the viewer shows them. `cache_images` decodes
+    // images into the image cache, which only pays off for messages that get displayed
+    async fn parse_messages_from_file(&self, file_path: &Path, cache_images: bool) -> Result<Vec<ChatMessage>> {
+        let raw_messages = self.read_raw_messages(file_path).await?;
+        let (raw_messages, mut sidechains) = self.split_sidechains(raw_messages, cache_images);
+        let mut messages = self.build_message_list(raw_messages.iter(), cache_images);
+        self.attach_sidechains(&mut messages, &mut sidechains);
+        Ok(messages)
+    }
+
+    /// Reads the user and assistant lines of a session file in file order.
+    async fn read_raw_messages(&self, file_path: &Path) -> Result<Vec<RawJsonlMessage>> {
+        let file = fs::File::open(file_path).await.map_err(|e| CommandError::io(e, file_path))?;
+        let reader = BufReader::new(file);
+        let mut lines = reader.split(b'\n');
+        let mut raw_messages = Vec::new();
+        let mut line_number = 0;
+
+        while let Some(line) = self.next_line(&mut lines, file_path, &mut line_number).await? {
+            if let Some(raw_msg) = self.parse_message_line(&line) {
+                raw_messages.push(raw_msg);
+            }
         }
+
+        Ok(raw_messages)
     }
 
-    async fn build_summary_index(&self, project_path: &Path) -> HashMap<String, String> {
-        let mut summary_index = HashMap::new();
-        
-        if let Ok(mut entries) = fs::read_dir(project_path).await {
-            while let Some(entry) = entries.next_entry().await.ok().flatten() {
-                if entry.file_type().await.ok().map_or(false, |ft| ft.is_file()) {
-                    let file_path = entry.path();
-                    if file_path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
-                        if let Ok(file) = fs::File::open(&file_path).await {
-                            let reader = BufReader::new(file);
-                            let mut lines = reader.lines();
-                            
-                            while let Some(line) = lines.next_line().await.ok().flatten() {
-                                if line.trim().is_empty() {
-                                    continue;
-                                }
-                                
-                                if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&line) {
-                                    if let Some(line_type) = json_value.get("type").and_then(|v| v.as_str()) {
-                                        if line_type == "summary" {
-                                            if let (Some(leaf_uuid), Some(summary)) = (
-                                                json_value.get("leafUuid").and_then(|v| v.as_str()),
-                                                json_value.get("summary").and_then(|v| v.as_str())
-                                            ) {
-                                                summary_index.insert(leaf_uuid.to_string(), summary.to_string());
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+    // Reads the next line of a session file, failing with its line number when
+    // the file can't be read. Bytes that aren't valid UTF-8 are replaced so that
+    // one damaged line doesn't make the rest of the file unreadable
+    async fn next_line(
+        &self,
+        lines: &mut Split<BufReader<fs::File>>,
+        file_path: &Path,
+        line_number: &mut usize,
+    ) -> Result<Option<String>> {
+        *line_number += 1;
+        let segment = lines
+            .next_segment()
+            .await
+            .map_err(|e| CommandError::io(e, file_path).with_line(*line_number))?;
+        Ok(segment.map(|bytes| {
+            let bytes = bytes.strip_suffix(b"\r").unwrap_or(&bytes);
+            String::from_utf8_lossy(bytes).into_owned()
+        }))
+    }
+
+    // Parses a JSONL line if it holds a user, assistant or system message
+    fn parse_message_line(&self, line: &str) -> Option<RawJsonlMessage> {
+        if line.trim().is_empty() {
+            return None;
+        }
+
+        // First check if this is a summary object - if so, skip it
+        if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(line) {
+            if let Some(line_type) = json_value.get("type").and_then(|v| v.as_str()) {
+                if line_type == "summary" {
+                    return None; // Skip summary objects
                 }
             }
         }
-        
-        summary_index
+
+        let mut raw_msg = serde_json::from_str::<RawJsonlMessage>(line).ok()?;
+        match raw_msg.message_type.as_str() {
+            "user" | "assistant" if !raw_msg.message.content.is_null() => Some(raw_msg),
+            // System records keep their text next to the other fields instead of in a message
+            "system" => {
+                raw_msg.message.content = raw_msg.content.take().unwrap_or_else(|| serde_json::Value::String(String::new()));
+                Some(raw_msg)
+            }
+            _ => None,
+        }
     }
 
-    async fn parse_messages_from_file(&self, file_path: &Path) -> Result<Vec<ChatMessage>> {
-        let file = fs::File::open(file_path).await?;
-        let reader = BufReader::new(file);
-        let mut lines = reader.lines();
+    /// Reads the messages appended to a session file since byte `offset`, for
+    /// following a session that is still being written. Pass the returned
+    /// `next_offset` to the next call.
+    pub async fn read_session_tail(&self, session_id: &str, offset: u64) -> Result<SessionTail> {
+        let jsonl_path = self.find_session_file(session_id).await?;
+        let mut file = fs::File::open(&jsonl_path).await.map_err(|e| CommandError::io(e, &jsonl_path))?;
+        let size = file.metadata().await?.len();
+
+        // A file shorter than the offset was rewritten, so read it from the start
+        let reset = offset > size;
+        let start = if reset { 0 } else { offset };
+        file.seek(SeekFrom::Start(start)).await?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).await?;
+
+        // The last line may still be in the middle of being written, leave it for the next read
+        let complete = bytes.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        let text = String::from_utf8_lossy(&bytes[..complete]);
+        let raw_messages: Vec<RawJsonlMessage> = text.lines().filter_map(|line| self.parse_message_line(line)).collect();
+
+        Ok(SessionTail {
+            messages: self.build_message_list(raw_messages.iter(), true),
+            next_offset: start + complete as u64,
+            reset,
+        })
+    }
+
+    // Converts lines into the messages shown by the viewer, folding streamed
+    // assistant chunks and tool results into the message they belong to
+    fn build_message_list<'a>(
+        &self,
+        raw_messages: impl Iterator<Item = &'a RawJsonlMessage>,
+        cache_images: bool,
+    ) -> Vec<ChatMessage> {
         let mut messages = Vec::new();
 
-        while let Some(line) = lines.next_line().await? {
-            if line.trim().is_empty() {
-                continue;
+        for raw_msg in raw_messages {
+            if let Ok(chat_msg) = self.convert_raw_to_chat_message(raw_msg, cache_images) {
+                // System records such as hook runs can sit between a tool call and its
+                // result, so merging looks past them to the last conversation message
+                let conversation_end = messages
+                    .iter()
+                    .rposition(|message: &ChatMessage| message.message_type != "system")
+                    .map_or(0, |i| i + 1);
+                let conversation = &mut messages[..conversation_end];
+
+                // Check if we should merge this message with the previous one based on message ID
+                if self.should_merge_by_message_id(&chat_msg, raw_msg, conversation) {
+                    self.merge_with_previous_by_id(&chat_msg, conversation);
+                } else if self.should_merge_tool_results_with_assistant(&chat_msg, conversation) {
+                    self.merge_tool_results_with_assistant(&chat_msg, conversation);
+                } else {
+                    messages.push(chat_msg);
+                }
             }
+        }
 
-            // First check if this is a summary object - if so, skip it
-            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&line) {
-                if let Some(line_type) = json_value.get("type").and_then(|v| v.as_str()) {
-                    if line_type == "summary" {
-                        continue; // Skip summary objects
+        messages
+    }
+
+    /// Takes the sub-agent runs started by Task tool calls out of the main thread.
+    /// Returns the remaining lines and each run's messages keyed by the tool_use id
+    /// of the call that started it. Runs that can't be tied to a call stay in place.
+    fn split_sidechains(
+        &self,
+        raw_messages: Vec<RawJsonlMessage>,
+        cache_images: bool,
+    ) -> (Vec<RawJsonlMessage>, HashMap<String, Vec<ChatMessage>>) {
+        // Sidechain lines link to each other by parentUuid, every root starts a run
+        let mut runs: Vec<Vec<usize>> = Vec::new();
+        let mut run_of: HashMap<&str, usize> = HashMap::new();
+        for (i, raw_msg) in raw_messages.iter().enumerate().filter(|(_, raw_msg)| raw_msg.is_sidechain) {
+            let parent_run = raw_msg.parent_uuid.as_deref().and_then(|uuid| run_of.get(uuid).copied());
+            let run = parent_run.unwrap_or_else(|| {
+                runs.push(Vec::new());
+                runs.len() - 1
+            });
+            runs[run].push(i);
+            run_of.insert(raw_msg.uuid.as_str(), run);
+        }
+
+        if runs.is_empty() {
+            return (raw_messages, HashMap::new());
+        }
+
+        // (tool_use id, prompt, timestamp) of every Task call in the main thread
+        let mut task_calls: Vec<(String, String, &str)> = Vec::new();
+        for raw_msg in raw_messages.iter().filter(|raw_msg| !raw_msg.is_sidechain) {
+            let Some(blocks) = raw_msg.message.content.as_array() else {
+                continue;
+            };
+            for block in blocks {
+                if block.get("type").and_then(|v| v.as_str()) == Some("tool_use")
+                    && block.get("name").and_then(|v| v.as_str()) == Some("Task")
+                {
+                    if let Some(id) = block.get("id").and_then(|v| v.as_str()) {
+                        let prompt = block
+                            .get("input")
+                            .and_then(|input| input.get("prompt"))
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+                        task_calls.push((id.to_string(), prompt.trim().to_string(), raw_msg.timestamp.as_str()));
                     }
                 }
             }
+        }
 
-            if let Ok(raw_msg) = serde_json::from_str::<RawJsonlMessage>(&line) {
-                if raw_msg.message_type == "user" || raw_msg.message_type == "assistant" {
-                    if let Ok(chat_msg) = self.convert_raw_to_chat_message(&raw_msg) {
-                        // Check if we should merge this message with the previous one based on message ID
-                        if self.should_merge_by_message_id(&chat_msg, &raw_msg, &messages) {
-                            self.merge_with_previous_by_id(&chat_msg, &mut messages);
-                        } else if self.should_merge_tool_results_with_assistant(&chat_msg, &messages) {
-                            self.merge_tool_results_with_assistant(&chat_msg, &mut messages);
-                        } else {
-                            messages.push(chat_msg);
+        // A run starts with the prompt of its Task call as the user message. Failing
+        // that, it belongs to the latest call made before the run started
+        let mut run_calls: Vec<Option<String>> = vec![None; runs.len()];
+        for (run, indices) in runs.iter().enumerate() {
+            let first = &raw_messages[indices[0]];
+            let prompt = match &first.message.content {
+                serde_json::Value::String(text) => text.trim().to_string(),
+                content => content
+                    .as_array()
+                    .and_then(|blocks| blocks.iter().find_map(|block| block.get("text").and_then(|v| v.as_str())))
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+            };
+
+            let is_free = |id: &String| !run_calls.contains(&Some(id.clone()));
+            let by_prompt = task_calls
+                .iter()
+                .find(|(id, task_prompt, _)| !prompt.is_empty() && *task_prompt == prompt && is_free(id));
+            let by_time = || {
+                task_calls
+                    .iter()
+                    .filter(|(id, _, timestamp)| *timestamp <= first.timestamp.as_str() && is_free(id))
+                    .max_by_key(|(_, _, timestamp)| *timestamp)
+            };
+            run_calls[run] = by_prompt.or_else(by_time).map(|(id, _, _)| id.clone());
+        }
+
+        let mut attached = vec![false; raw_messages.len()];
+        let mut sidechains = HashMap::new();
+        for (indices, call) in runs.iter().zip(run_calls) {
+            if let Some(tool_use_id) = call {
+                indices.iter().for_each(|&i| attached[i] = true);
+                sidechains.insert(tool_use_id, self.build_message_list(indices.iter().map(|&i| &raw_messages[i]), cache_images));
+            }
+        }
+
+        let main_thread = raw_messages
+            .into_iter()
+            .zip(attached)
+            .filter(|(_, attached)| !attached)
+            .map(|(raw_msg, _)| raw_msg)
+            .collect();
+        (main_thread, sidechains)
+    }
+
+    // Moves each sub-agent run onto the tool_use block of the Task call that started it
+    fn attach_sidechains(&self, messages: &mut [ChatMessage], sidechains: &mut HashMap<String, Vec<ChatMessage>>) {
+        for message in messages.iter_mut() {
+            if let MessageContent::Mixed(blocks) = &mut message.content {
+                for block in blocks.iter_mut() {
+                    if let ContentBlock::ToolUse { id, sidechain, .. } = block {
+                        if let Some(run) = sidechains.remove(id.as_str()) {
+                            *sidechain = Some(run);
                         }
                     }
                 }
             }
         }
+    }
 
-        Ok(messages)
+    pub async fn get_conversation_tree(&self, session_id: &str) -> Result<ConversationTree> {
+        let jsonl_path = self.find_session_file(session_id).await?;
+        let raw_messages = self.read_raw_messages(&jsonl_path).await?;
+        let (raw_messages, mut sidechains) = self.split_sidechains(raw_messages, true);
+        let summaries = self.project_summaries(&jsonl_path).await;
+
+        let graph = ConversationGraph::new(&raw_messages);
+        let active_leaf = graph.active_leaf(&summaries);
+        let active_path = active_leaf.map(|leaf| graph.path_to(leaf)).unwrap_or_default();
+        let uuid_of = |node: usize| raw_messages[node].uuid.clone();
+
+        let nodes = raw_messages
+            .iter()
+            .enumerate()
+            .filter_map(|(i, raw_msg)| {
+                let mut message = self.convert_raw_to_chat_message(raw_msg, true).ok()?;
+                self.attach_sidechains(std::slice::from_mut(&mut message), &mut sidechains);
+                Some(ConversationNode {
+                    uuid: raw_msg.uuid.clone(),
+                    parent_uuid: graph.parent(i).map(uuid_of),
+                    children: graph.children(i).iter().map(|&child| uuid_of(child)).collect(),
+                    message,
+                    is_active: active_path.contains(&i),
+                    summary: summaries.get(&raw_msg.uuid).cloned(),
+                })
+            })
+            .collect();
+
+        Ok(ConversationTree {
+            session_id: session_id.to_string(),
+            nodes,
+            roots: graph.roots().into_iter().map(uuid_of).collect(),
+            leaves: graph.leaves().into_iter().map(uuid_of).collect(),
+            active_leaf: active_leaf.map(uuid_of),
+        })
     }
 
+    /// Returns the messages from the root down to `leaf_uuid`, merged like
+    /// `get_chat_messages` does. Without a leaf the active branch is used.
+    pub async fn get_branch_messages(&self, session_id: &str, leaf_uuid: Option<&str>) -> Result<Vec<ChatMessage>> {
+        let jsonl_path = self.find_session_file(session_id).await?;
+        let raw_messages = self.read_raw_messages(&jsonl_path).await?;
+        let (raw_messages, mut sidechains) = self.split_sidechains(raw_messages, true);
+        let graph = ConversationGraph::new(&raw_messages);
+
+        let leaf = match leaf_uuid {
+            Some(leaf_uuid) => graph
+                .find(leaf_uuid)
+                .ok_or_else(|| {
+                    let message = format!("Message {} not found in session {}", leaf_uuid, session_id);
+                    CommandError::new(CommandErrorKind::InvalidInput, message)
+                })?,
+            None => match graph.active_leaf(&self.project_summaries(&jsonl_path).await) {
+                Some(leaf) => leaf,
+                None => return Ok(Vec::new()),
+            },
+        };
 
+        let path = graph.path_to(leaf);
+        let mut messages = self.build_message_list(path.into_iter().map(|node| &raw_messages[node]), true);
+        self.attach_sidechains(&mut messages, &mut sidechains);
+        Ok(messages)
+    }
+
+    // Summaries by leaf uuid from every session file next to `file_path`, since
+    // Claude Code writes them into the session that continues a conversation
+    async fn project_summaries(&self, file_path: &Path) -> HashMap<String, String> {
+        let Some(project_dir) = file_path.parent() else {
+            return HashMap::new();
+        };
 
+        self.load_project_files(project_dir)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|indexed| indexed.summaries)
+            .collect()
+    }
 
     fn should_merge_by_message_id(&self, current_msg: &ChatMessage, raw_msg: &RawJsonlMessage, messages: &[ChatMessage]) -> bool {
         // Check if this message has the same ID as the previous message
@@ -295,22 +823,20 @@ impl ChatService {
         false
     }
 
-    fn merge_with_previous_by_id(&self, current_msg: &ChatMessage, messages: &mut Vec<ChatMessage>) {
+    fn merge_with_previous_by_id(&self, current_msg: &ChatMessage, messages: &mut [ChatMessage]) {
         if let Some(prev_msg) = messages.last_mut() {
+            // Every chunk carries the usage of the whole message, the last one is final
+            if current_msg.usage.is_some() {
+                prev_msg.usage = current_msg.usage;
+                prev_msg.cost_usd = current_msg.cost_usd;
+            }
+            prev_msg.images.extend(current_msg.images.iter().cloned());
+
             // Merge content blocks from current message into previous message
             match (&mut prev_msg.content, &current_msg.content) {
                 (MessageContent::Text(prev_text), MessageContent::Mixed(current_blocks)) => {
                     // Convert previous text to mixed content and add current blocks
-                    let mut blocks = vec![ContentBlock {
-                        block_type: "text".to_string(),
-                        text: Some(prev_text.clone()),
-                        name: None,
-                        input: None,
-                        tool_use_id: None,
-                        content: None,
-                        tool_use_result: None,
-                        thinking: None,
-                    }];
+                    let mut blocks = vec![ContentBlock::Text { text: prev_text.clone() }];
                     blocks.extend(current_blocks.clone());
                     prev_msg.content = MessageContent::Mixed(blocks);
                 }
@@ -320,16 +846,7 @@ impl ChatService {
                 }
                 (MessageContent::Mixed(prev_blocks), MessageContent::Text(current_text)) => {
                     // Add current text as a text block
-                    prev_blocks.push(ContentBlock {
-                        block_type: "text".to_string(),
-                        text: Some(current_text.clone()),
-                        name: None,
-                        input: None,
-                        tool_use_id: None,
-                        content: None,
-                        tool_use_result: None,
-                        thinking: None,
-                    });
+                    prev_blocks.push(ContentBlock::Text { text: current_text.clone() });
                 }
                 _ => {} // Other combinations are less common
             }
@@ -345,7 +862,7 @@ impl ChatService {
         // Check if current message contains tool results
         let has_tool_results = match &current_msg.content {
             MessageContent::Mixed(blocks) => {
-                blocks.iter().any(|block| block.block_type == "tool_result")
+                blocks.iter().any(|block| matches!(block, ContentBlock::ToolResult { .. }))
             }
             _ => false,
         };
@@ -362,27 +879,38 @@ impl ChatService {
         }
     }
 
-    fn merge_tool_results_with_assistant(&self, current_msg: &ChatMessage, messages: &mut Vec<ChatMessage>) {
+    fn merge_tool_results_with_assistant(&self, current_msg: &ChatMessage, messages: &mut [ChatMessage]) {
         if let Some(prev_msg) = messages.last_mut() {
             if let MessageContent::Mixed(current_blocks) = &current_msg.content {
-                // Find tool results in current message
-                let tool_results: Vec<&ContentBlock> = current_blocks
-                    .iter()
-                    .filter(|block| block.block_type == "tool_result")
-                    .collect();
-
                 // Add tool results to the previous assistant message
                 if let MessageContent::Mixed(prev_blocks) = &mut prev_msg.content {
                     // Match tool results to tool calls by tool_use_id
-                    for tool_result in tool_results {
-                        if let Some(tool_use_id) = &tool_result.tool_use_id {
-                            // Find the matching tool call and add the result
-                            for block in prev_blocks.iter_mut() {
-                                if block.block_type == "tool_use" && 
-                                   block.tool_use_id.as_ref() == Some(tool_use_id) {
+                    for tool_result in current_blocks {
+                        let ContentBlock::ToolResult { tool_use_id, content, is_error, tool_use_result, images } = tool_result else {
+                            continue;
+                        };
+                        // Find the matching tool call and add the result
+                        for block in prev_blocks.iter_mut() {
+                            if let ContentBlock::ToolUse {
+                                id,
+                                content: call_content,
+                                is_error: call_is_error,
+                                name,
+                                input,
+                                tool_use_result: call_result,
+                                images: call_images,
+                                diff,
+                                ..
+                            } = block
+                            {
+                                if id == tool_use_id {
                                     // Add result data to the tool use block
-                                    block.content = tool_result.content.clone();
-                                    block.tool_use_result = tool_result.tool_use_result.clone();
+                                    *call_content = content.clone();
+                                    *call_is_error = *is_error;
+                                    *call_result = tool_use_result.clone();
+                                    *call_images = images.clone();
+                                    // The result's structuredPatch has the real file line numbers
+                                    *diff = file_diff(name, input, tool_use_result.as_ref());
                                     break;
                                 }
                             }
@@ -393,15 +921,15 @@ impl ChatService {
         }
     }
 
-    fn convert_raw_to_chat_message(&self, raw: &RawJsonlMessage) -> Result<ChatMessage> {
-        let mut content = self.parse_message_content(&raw.message.content)?;
+    fn convert_raw_to_chat_message(&self, raw: &RawJsonlMessage, cache_images: bool) -> Result<ChatMessage> {
+        let mut content = self.parse_message_content(&raw.message.content, cache_images)?;
         
         // If this is a tool result message and has toolUseResult, add it to content blocks
         if raw.message_type == "user" && raw.tool_use_result.is_some() {
             if let MessageContent::Mixed(ref mut blocks) = content {
                 for block in blocks.iter_mut() {
-                    if block.block_type == "tool_result" {
-                        block.tool_use_result = raw.tool_use_result.clone();
+                    if let ContentBlock::ToolResult { tool_use_result, .. } = block {
+                        *tool_use_result = raw.tool_use_result.clone().map(Self::without_inline_image);
                     }
                 }
             }
@@ -414,7 +942,7 @@ impl ChatService {
             raw.uuid.clone()
         };
         
-        Ok(ChatMessage {
+        let mut chat_msg = ChatMessage {
             uuid,
             parent_uuid: raw.parent_uuid.clone(),
             timestamp: raw.timestamp.clone(),
@@ -424,146 +952,396 @@ impl ChatService {
             cwd: Some(raw.cwd.clone()),
             version: Some(raw.version.clone()),
             model: raw.message.model.clone(),
-        })
+            is_sidechain: raw.is_sidechain,
+            usage: raw.message.usage,
+            cost_usd: raw.message.usage.map(|usage| {
+                self.pricing.cost(raw.message.model.as_deref().unwrap_or_default(), &usage)
+            }),
+            kind: MessageKind::Conversation,
+            images: Vec::new(),
+        };
+        chat_msg.images = chat_msg.attached_images();
+        chat_msg.kind = MessageKind::from_raw(raw, &chat_msg.extract_text());
+        Ok(chat_msg)
     }
 
-    fn parse_message_content(&self, content: &serde_json::Value) -> Result<MessageContent> {
+    fn parse_message_content(&self, content: &serde_json::Value, cache_images: bool) -> Result<MessageContent> {
         match content {
             serde_json::Value::String(text) => Ok(MessageContent::Text(text.clone())),
             serde_json::Value::Array(blocks) => {
-                let mut content_blocks = Vec::new();
-                
-                for block in blocks {
-                    if let Ok(content_block) = self.parse_content_block(block) {
-                        content_blocks.push(content_block);
-                    }
-                }
-                
+                // Blocks that don't match their declared type are kept as Unknown
+                let content_blocks = blocks
+                    .iter()
+                    .map(|block| {
+                        let mut content_block =
+                            serde_json::from_value(block.clone()).unwrap_or_else(|_| ContentBlock::Unknown(block.clone()));
+                        match &mut content_block {
+                            ContentBlock::ToolUse { name, input, diff, .. } => *diff = file_diff(name, input, None),
+                            ContentBlock::ToolResult { images, .. } if cache_images => *images = self.tool_result_images(block),
+                            ContentBlock::Image { source, image } if cache_images => {
+                                *image = self.images.store(source);
+                                // The webview loads cached images by id, so the data needn't cross IPC.
+                                // It stays when the image wasn't written anywhere, e.g. for exports
+                                if let (Some(_), Some(source)) = (image, source.as_object_mut()) {
+                                    if self.images.is_persistent() {
+                                        source.remove("data");
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                        content_block
+                    })
+                    .collect();
+
                 Ok(MessageContent::Mixed(content_blocks))
             }
             _ => Ok(MessageContent::Text(content.to_string())),
         }
     }
 
-    fn parse_content_block(&self, block: &serde_json::Value) -> Result<ContentBlock> {
-        let block_type = block.get("type")
-            .and_then(|v| v.as_str())
-            .unwrap_or("unknown")
-            .to_string();
+    // Image blocks of an array-form tool result, e.g. Read on a screenshot or an MCP
+    // tool returning a chart, decoded into the image cache
+    fn tool_result_images(&self, block: &serde_json::Value) -> Vec<ImageRef> {
+        let Some(items) = block.get("content").and_then(|v| v.as_array()) else {
+            return Vec::new();
+        };
+        items
+            .iter()
+            .filter(|item| item.get("type").and_then(|v| v.as_str()) == Some("image"))
+            .filter_map(|item| self.images.store(item.get("source")?))
+            .collect()
+    }
 
-        let text = block.get("text")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
+    // Read reports an image it returned with the whole file again as base64, which
+    // is already served from the tool result's image block
+    fn without_inline_image(mut tool_use_result: serde_json::Value) -> serde_json::Value {
+        if let Some(file) = tool_use_result.get_mut("file").and_then(|v| v.as_object_mut()) {
+            file.remove("base64");
+        }
+        tool_use_result
+    }
 
-        let name = block.get("name")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
+    pub async fn search_chats(&self, query: &str, mode: SearchMode) -> Result<Vec<SearchResult>, SearchError> {
+        let mut results = self.search_chats_page(query, mode, None, MAX_RESULTS).await?.results;
+
+        // Scanned results come in file order, so at least put content matches first
+        if !self.uses_search_index(mode) {
+            results.sort_by_key(|result| match result.match_type.as_str() {
+                "content" => 0,
+                "thinking" => 1,
+                "tool_name" => 2,
+                "tool_input" => 3,
+                "tool_result" => 4,
+                _ => 5,
+            });
+        }
 
-        let input = block.get("input").cloned();
+        Ok(results)
+    }
 
-        // For tool_use blocks, the ID is in the "id" field
-        // For tool_result blocks, the ID is in the "tool_use_id" field
-        let tool_use_id = if block_type == "tool_use" {
-            block.get("id")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string())
-        } else {
-            block.get("tool_use_id")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string())
+    /// Returns up to `limit` results starting at `cursor`, which is either None for
+    /// the first page or the `next_cursor` of the previous page.
+    pub async fn search_chats_page(
+        &self,
+        query: &str,
+        mode: SearchMode,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<SearchPage, SearchError> {
+        let offset = match cursor {
+            Some(cursor) => cursor.parse::<usize>().map_err(|_| SearchError::InvalidQuery {
+                message: format!("Invalid search cursor: {}", cursor),
+            })?,
+            None => 0,
         };
 
-        let content = block.get("content")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
+        let matcher = SearchMatcher::new(query, mode)?;
+        if matcher.is_empty() {
+            return Ok(SearchPage { results: Vec::new(), next_cursor: None });
+        }
 
-        let thinking = block.get("thinking")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
+        // Later pages continue the search the first page started
+        let session_titles = match cursor {
+            None => self.start_search(mode).await?,
+            Some(_) => self.search_titles.read().unwrap().clone(),
+        };
 
-        Ok(ContentBlock {
-            block_type,
-            text,
-            name,
-            input,
-            tool_use_id,
-            content,
-            tool_use_result: None, // Will be populated later if needed
-            thinking,
+        let (results, next_offset) = self.search_range(&matcher, offset, limit, &session_titles).await?;
+        Ok(SearchPage {
+            results,
+            next_cursor: next_offset.map(|offset| offset.to_string()),
         })
     }
 
-    pub async fn search_chats(&self, query: &str) -> Result<Vec<SearchResult>> {
+    /// Runs a search and hands the results to `on_batch` as they are found instead of
+    /// collecting them first. Starting a new streaming search or calling `cancel_search`
+    /// stops the running one; returns false if this search was cancelled.
+    pub async fn search_chats_streaming(
+        &self,
+        search_id: u64,
+        query: &str,
+        mode: SearchMode,
+        mut on_batch: impl FnMut(Vec<SearchResult>),
+    ) -> Result<bool, SearchError> {
+        self.active_search.store(search_id, Ordering::SeqCst);
+        let is_cancelled = || self.active_search.load(Ordering::SeqCst) != search_id;
+
+        let matcher = SearchMatcher::new(query, mode)?;
+        if matcher.is_empty() {
+            return Ok(true);
+        }
+
+        let session_titles = self.start_search(mode).await?;
+        if self.uses_search_index(mode) {
+            let mut offset = 0;
+            loop {
+                let (results, next_offset) = self.search_range(&matcher, offset, STREAM_BATCH_SIZE, &session_titles).await?;
+                if is_cancelled() {
+                    return Ok(false);
+                }
+                if !results.is_empty() {
+                    on_batch(results);
+                }
+                match next_offset {
+                    Some(next_offset) => offset = next_offset,
+                    None => break,
+                }
+            }
+            return Ok(true);
+        }
+
+        let mut batch = Vec::new();
+        for file_path in self.list_session_files().await? {
+            if is_cancelled() {
+                return Ok(false);
+            }
+            if let Ok(documents) = self.search_file_streaming(&file_path, &matcher).await {
+                batch.extend(self.group_search_results(documents, &matcher, &session_titles));
+            }
+            if batch.len() >= STREAM_BATCH_SIZE {
+                on_batch(std::mem::take(&mut batch));
+            }
+        }
+        if !batch.is_empty() {
+            on_batch(batch);
+        }
+
+        Ok(true)
+    }
+
+    pub fn cancel_search(&self, search_id: u64) {
+        // Only clear the active search if a newer one hasn't replaced it already
+        self.active_search
+            .compare_exchange(search_id, 0, Ordering::SeqCst, Ordering::SeqCst)
+            .ok();
+    }
+
+    fn uses_search_index(&self, mode: SearchMode) -> bool {
+        // Arbitrary regexes can't be answered from an inverted index, so they always scan
+        self.search_index.is_some() && mode != SearchMode::Regex
+    }
+
+    // Brings the search index up to date with the session files and takes the
+    // session titles, once per search rather than for every page or batch
+    async fn start_search(&self, mode: SearchMode) -> Result<Arc<HashMap<String, String>>, SearchError> {
+        if let (Some(search_index), true) = (&self.search_index, self.uses_search_index(mode)) {
+            self.refresh_search_index(search_index).await?;
+        }
+        let session_titles = Arc::new(self.session_titles());
+        *self.search_titles.write().unwrap() = session_titles.clone();
+        Ok(session_titles)
+    }
+
+    // Returns the results in a window of the search along with the offset the next
+    // window starts at, None once there is nothing left. Fetches one more than `limit`
+    // to find out whether anything follows.
+    async fn search_range(
+        &self,
+        matcher: &SearchMatcher,
+        offset: usize,
+        limit: usize,
+        session_titles: &HashMap<String, String>,
+    ) -> Result<(Vec<SearchResult>, Option<usize>), SearchError> {
+        if let (Some(search_index), true) = (&self.search_index, self.uses_search_index(matcher.mode)) {
+            // Offsets count indexed fields rather than messages, since several fields of
+            // one message group into a single result. A message whose hits straddle a
+            // window boundary therefore shows up in both windows.
+            let mut documents = search_index.search(&matcher.query, matcher.mode == SearchMode::Fuzzy, offset, limit + 1)?;
+            let next_offset = (documents.len() > limit).then_some(offset + limit);
+            documents.truncate(limit);
+            return Ok((self.group_search_results(documents, matcher, session_titles), next_offset));
+        }
+
         let mut results = Vec::new();
-        let query_lower = query.to_lowercase();
-        // const MAX_RESULTS: usize = 50; // Limit results for performance
-        
-        // Get all project directories
-        let mut project_entries = fs::read_dir(&self.projects_path).await?;
+        let mut skipped = 0;
         
-        while let Some(project_entry) = project_entries.next_entry().await? {
-            if !project_entry.file_type().await?.is_dir() {
-                continue;
+        for file_path in self.list_session_files().await? {
+            // Stream search through this file
+            if let Ok(documents) = self.search_file_streaming(&file_path, matcher).await {
+                for result in self.group_search_results(documents, matcher, session_titles) {
+                    if skipped < offset {
+                        skipped += 1;
+                    } else {
+                        results.push(result);
+                    }
+                }
+
+                // Early termination once the requested window is filled
+                if results.len() > limit {
+                    break;
+                }
             }
-            
-            let project_path = project_entry.path();
-            let mut file_entries = fs::read_dir(&project_path).await?;
-            
-            while let Some(file_entry) = file_entries.next_entry().await? {
-                if !file_entry.file_type().await?.is_file() {
-                    continue;
+        }
+
+        let next_offset = (results.len() > limit).then_some(offset + limit);
+        results.truncate(limit);
+        Ok((results, next_offset))
+    }
+
+    async fn list_session_files(&self) -> Result<Vec<PathBuf>> {
+        let project_files = self.project_session_files(None).await?;
+        Ok(project_files.into_iter().flat_map(|(_, session_files)| session_files).collect())
+    }
+
+    // The session files of each project folder, of only the one in `project_path` or
+    // across all data roots. Roots can overlap, e.g. a backup that mirrors the live
+    // directory, so a file already found under the same folder name in an earlier
+    // root is left out and the first root wins
+    async fn project_session_files(&self, project_path: Option<&Path>) -> Result<Vec<(PathBuf, Vec<PathBuf>)>> {
+        if let Some(project_path) = project_path {
+            return Ok(vec![(project_path.to_path_buf(), self.session_files_in(project_path).await?)]);
+        }
+
+        let mut project_files = Vec::new();
+        let mut seen_files = HashSet::new();
+        for project_dir in self.project_dirs().await? {
+            let folder = PathBuf::from(project_dir.file_name().unwrap_or_default());
+            let mut session_files = self.session_files_in(&project_dir).await?;
+            session_files.retain(|file_path| seen_files.insert(folder.join(file_path.file_name().unwrap_or_default())));
+            project_files.push((project_dir, session_files));
+        }
+        Ok(project_files)
+    }
+
+    // Project folders of every data root, in root order
+    async fn project_dirs(&self) -> Result<Vec<PathBuf>> {
+        let mut project_dirs = Vec::new();
+        for projects_dir in self.data_roots.require_projects_dirs()? {
+            let mut entries = fs::read_dir(&projects_dir).await.map_err(|e| CommandError::io(e, &projects_dir))?;
+            while let Some(entry) = entries.next_entry().await? {
+                if entry.file_type().await?.is_dir() {
+                    project_dirs.push(entry.path());
                 }
-                
-                let file_path = file_entry.path();
-                if file_path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+            }
+        }
+        Ok(project_dirs)
+    }
+
+    /// The session files with lines that were skipped while parsing, across all
+    /// projects or only the one in `project_path`.
+    pub async fn get_parse_diagnostics(&self, project_path: Option<&Path>) -> Result<Vec<ParseDiagnostics>> {
+        let mut diagnostics = Vec::new();
+        for (project_dir, session_files) in self.project_session_files(project_path).await? {
+            for file_path in session_files {
+                let stamp = FileStamp::from_metadata(&fs::metadata(&file_path).await?);
+                let Some(indexed) = self.load_session_file(&file_path, stamp).await else {
+                    continue;
+                };
+                if indexed.issue_count == 0 {
                     continue;
                 }
-                
-                // Stream search through this file
-                if let Ok(file_results) = self.search_file_streaming(&file_path, &query_lower).await {
-                    results.extend(file_results);
-                    
-                    // Early termination if we have enough results
-                    // if results.len() >= MAX_RESULTS {
-                    //     results.truncate(MAX_RESULTS);
-                    //     return Ok(results);
-                    // }
-                }
+                diagnostics.push(ParseDiagnostics {
+                    session_id: indexed.session.map(|session| session.id),
+                    project_path: project_dir.to_string_lossy().to_string(),
+                    file_path: file_path.to_string_lossy().to_string(),
+                    issue_count: indexed.issue_count,
+                    issues: indexed.issues,
+                });
             }
         }
-        
-        // Sort by relevance (could be improved with scoring)
-        results.sort_by(|a, b| {
-            // Prefer content matches over tool matches
-            let a_priority = match a.match_type.as_str() {
-                "content" => 0,
-                "thinking" => 1,
-                "tool_name" => 2,
-                "tool_input" => 3,
-                "tool_result" => 4,
-                _ => 5,
-            };
-            let b_priority = match b.match_type.as_str() {
-                "content" => 0,
-                "thinking" => 1,
-                "tool_name" => 2,
-                "tool_input" => 3,
-                "tool_result" => 4,
-                _ => 5,
-            };
-            a_priority.cmp(&b_priority)
-        });
-        
-        Ok(results)
+
+        if let Some(index) = &self.session_index {
+            index.save().await.ok();
+        }
+        Ok(diagnostics)
+    }
+
+    pub fn get_data_roots(&self) -> Vec<DataRoot> {
+        self.data_roots.status()
+    }
+
+    /// Replaces the configured data roots, an empty list restores the default.
+    pub async fn set_data_roots(&self, roots: Vec<PathBuf>) -> Result<Vec<DataRoot>> {
+        self.data_roots.set_roots(roots).await?;
+        Ok(self.data_roots.status())
+    }
+
+    async fn session_files_in(&self, project_dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut session_files = Vec::new();
+        let mut entries = fs::read_dir(project_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_path = entry.path();
+            if entry.file_type().await?.is_file() && file_path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
+                session_files.push(file_path);
+            }
+        }
+        Ok(session_files)
+    }
+
+    async fn refresh_search_index(&self, search_index: &SearchIndex) -> Result<()> {
+        let mut current = Vec::new();
+        for file_path in self.list_session_files().await? {
+            let stamp = FileStamp::from_metadata(&fs::metadata(&file_path).await?);
+            current.push((file_path, stamp));
+        }
+
+        let changed = search_index.changed_files(&current);
+        let removed_any = search_index.remove_missing_files(&current);
+        if changed.is_empty() && !removed_any {
+            return Ok(());
+        }
+
+        for (file_path, stamp) in changed {
+            // Unreadable files are recorded with no documents so they aren't retried until they change
+            let documents = self.search_documents_from_file(&file_path).await.unwrap_or_default();
+            search_index.index_file(&file_path, stamp, documents)?;
+        }
+
+        search_index.commit()
+    }
+
+    async fn search_documents_from_file(&self, file_path: &Path) -> Result<Vec<SearchDocument>> {
+        let file = fs::File::open(file_path).await?;
+        let reader = BufReader::new(file);
+        let mut lines = reader.lines();
+        let mut documents = Vec::new();
+        let mut session_id: Option<String> = None;
+        let mut tool_names = HashMap::new();
+
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() || line.contains("\"type\":\"summary\"") {
+                continue;
+            }
+
+            if let Ok(raw_msg) = serde_json::from_str::<RawJsonlMessage>(&line) {
+                // Attribute every hit to the session the file was started with, like ChatSession does
+                let session_id = session_id.get_or_insert_with(|| raw_msg.session_id.clone());
+                documents.extend(self.search_documents(&raw_msg, session_id, &mut tool_names));
+            }
+        }
+
+        Ok(documents)
     }
     
-    async fn search_file_streaming(&self, file_path: &Path, query_lower: &str) -> Result<Vec<SearchResult>> {
-        let mut results = Vec::new();
+    async fn search_file_streaming(&self, file_path: &Path, matcher: &SearchMatcher) -> Result<Vec<SearchDocument>> {
+        let mut matches = Vec::new();
         let file = fs::File::open(file_path).await?;
         let reader = BufReader::new(file);
         let mut lines = reader.lines();
         
         let mut current_session_id: Option<String> = None;
+        let mut tool_names = HashMap::new();
         
         while let Some(line) = lines.next_line().await? {
             if line.trim().is_empty() {
@@ -575,8 +1353,9 @@ impl ChatService {
                 continue;
             }
             
-            // Fast query matching - check if line contains query before JSON parsing
-            if !line.to_lowercase().contains(query_lower) {
+            // Fast query matching - check if line can match before JSON parsing. Tool calls
+            // are always parsed so tool results further down can be attributed to their tool
+            if !matcher.could_match_line(&line.to_lowercase()) && !line.contains("\"type\":\"tool_use\"") {
                 // Still need to extract session ID for context
                 if current_session_id.is_none() {
                     if let Some(session_id) = self.extract_session_id_fast(&line) {
@@ -595,113 +1374,165 @@ impl ChatService {
                 
                 let session_id = current_session_id.as_ref().unwrap();
                 
-                // Only process user and assistant messages
-                if raw_msg.message_type != "user" && raw_msg.message_type != "assistant" {
-                    continue;
+                for document in self.search_documents(&raw_msg, session_id, &mut tool_names) {
+                    if matcher.matches(&document) {
+                        matches.push(document);
+                    }
                 }
-                
-                // Search in message content
-                if let Ok(content) = self.parse_message_content(&raw_msg.message.content) {
-                    match content {
-                        MessageContent::Text(text) => {
-                            if text.to_lowercase().contains(query_lower) {
-                                let snippet = self.create_snippet(&text, query_lower);
-                                results.push(SearchResult {
-                                    session_id: session_id.clone(),
-                                    message_uuid: raw_msg.uuid.clone(),
-                                    snippet,
-                                    match_type: "content".to_string(),
-                                });
+            }
+        }
+        
+        Ok(matches)
+    }
+
+    /// Turns matching documents into one result per message, keeping the order in
+    /// which each message was first hit.
+    fn group_search_results(
+        &self,
+        documents: Vec<SearchDocument>,
+        matcher: &SearchMatcher,
+        session_titles: &HashMap<String, String>,
+    ) -> Vec<SearchResult> {
+        let mut results: Vec<SearchResult> = Vec::new();
+        let mut positions: HashMap<(String, String), usize> = HashMap::new();
+
+        for document in documents {
+            let search_match = self.search_match(&document, matcher);
+            let key = (document.session_id.clone(), document.message_uuid.clone());
+
+            if let Some(&position) = positions.get(&key) {
+                results[position].matches.push(search_match);
+                continue;
+            }
+
+            let timestamp = chrono::DateTime::from_timestamp_millis(document.timestamp_ms)
+                .map(|timestamp| timestamp.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+                .unwrap_or_default();
+
+            positions.insert(key, results.len());
+            results.push(SearchResult {
+                session_title: session_titles.get(&document.session_id).cloned(),
+                session_id: document.session_id,
+                message_uuid: document.message_uuid,
+                // The first match is the best ranked one and represents the message
+                snippet: search_match.snippet.clone(),
+                match_type: search_match.match_type.clone(),
+                highlight: search_match.highlight.clone(),
+                project_path: document.project_path,
+                timestamp,
+                role: document.role,
+                model: document.model,
+                tool_name: document.tool_name,
+                matches: vec![search_match],
+            });
+        }
+
+        results
+    }
+
+    fn search_match(&self, document: &SearchDocument, matcher: &SearchMatcher) -> SearchMatch {
+        let (snippet, highlight) = if document.match_type == "tool_name" {
+            let prefix = "Tool: ";
+            let snippet = format!("{}{}", prefix, document.text);
+            let highlight = SnippetHighlight::new(&snippet, prefix.len()..snippet.len());
+            (snippet, Some(highlight))
+        } else {
+            // Center the snippet on the actual match
+            self.create_snippet(&document.text, matcher.find_span(&document.text))
+        };
+
+        SearchMatch {
+            match_type: document.match_type.clone(),
+            snippet,
+            highlight,
+            tool_name: document.tool_name.clone(),
+        }
+    }
+
+    fn session_titles(&self) -> HashMap<String, String> {
+        self.session_index
+            .as_ref()
+            .map(|index| index.session_titles())
+            .unwrap_or_default()
+    }
+
+    /// Splits a message into the fields that are searchable, one document per field.
+    /// `tool_names` maps tool_use ids seen so far in the file to their tool, so that
+    /// tool results (which only carry the id) can be filtered by tool as well.
+    fn search_documents(
+        &self,
+        raw_msg: &RawJsonlMessage,
+        session_id: &str,
+        tool_names: &mut HashMap<String, String>,
+    ) -> Vec<SearchDocument> {
+        let mut documents = Vec::new();
+
+        // Only process user and assistant messages
+        if raw_msg.message_type != "user" && raw_msg.message_type != "assistant" {
+            return documents;
+        }
+
+        // Converting (rather than just parsing the content) attaches toolUseResult to tool_result blocks
+        let Ok(chat_msg) = self.convert_raw_to_chat_message(raw_msg, false) else {
+            return documents;
+        };
+
+        let timestamp_ms = chrono::DateTime::parse_from_rfc3339(&raw_msg.timestamp)
+            .map(|timestamp| timestamp.timestamp_millis())
+            .unwrap_or_default();
+        let mut push = |match_type: &str, text: String, tool_name: Option<&String>| {
+            documents.push(SearchDocument {
+                session_id: session_id.to_string(),
+                message_uuid: raw_msg.uuid.clone(),
+                match_type: match_type.to_string(),
+                text,
+                tool_name: tool_name.cloned(),
+                role: raw_msg.message_type.clone(),
+                model: raw_msg.message.model.clone(),
+                project_path: raw_msg.cwd.clone(),
+                timestamp_ms,
+            });
+        };
+
+        match chat_msg.content {
+            MessageContent::Text(text) => push("content", text, None),
+            MessageContent::Mixed(blocks) => {
+                for block in blocks {
+                    match block {
+                        ContentBlock::Text { text } => push("content", text, None),
+                        ContentBlock::Thinking { thinking, .. } => push("thinking", thinking, None),
+                        ContentBlock::ToolUse { id, name, input, content, tool_use_result, .. } => {
+                            tool_names.insert(id, name.clone());
+                            push("tool_name", name.clone(), Some(&name));
+                            push("tool_input", serde_json::to_string(&input).unwrap_or_default(), Some(&name));
+                            if let Some(content) = content {
+                                push("tool_result", content, Some(&name));
+                            }
+                            if let Some(tool_use_result) = &tool_use_result {
+                                push("tool_structured_result", serde_json::to_string(tool_use_result).unwrap_or_default(), Some(&name));
                             }
                         }
-                        MessageContent::Mixed(blocks) => {
-                            for block in blocks {
-                                // Search in text blocks (message content)
-                                if let Some(text) = &block.text {
-                                    if text.to_lowercase().contains(query_lower) {
-                                        let snippet = self.create_snippet(text, query_lower);
-                                        results.push(SearchResult {
-                                            session_id: session_id.clone(),
-                                            message_uuid: raw_msg.uuid.clone(),
-                                            snippet,
-                                            match_type: "content".to_string(),
-                                        });
-                                    }
-                                }
-                                
-                                // Search in thinking blocks
-                                if let Some(thinking) = &block.thinking {
-                                    if thinking.to_lowercase().contains(query_lower) {
-                                        let snippet = self.create_snippet(thinking, query_lower);
-                                        results.push(SearchResult {
-                                            session_id: session_id.clone(),
-                                            message_uuid: raw_msg.uuid.clone(),
-                                            snippet,
-                                            match_type: "thinking".to_string(),
-                                        });
-                                    }
-                                }
-                                
-                                // Search in tool names
-                                if let Some(name) = &block.name {
-                                    if name.to_lowercase().contains(query_lower) {
-                                        results.push(SearchResult {
-                                            session_id: session_id.clone(),
-                                            message_uuid: raw_msg.uuid.clone(),
-                                            snippet: format!("Tool: {}", name),
-                                            match_type: "tool_name".to_string(),
-                                        });
-                                    }
-                                }
-                                
-                                // Search in tool input
-                                if let Some(input) = &block.input {
-                                    let input_text = serde_json::to_string(input).unwrap_or_default();
-                                    if input_text.to_lowercase().contains(query_lower) {
-                                        let snippet = self.create_snippet(&input_text, query_lower);
-                                        results.push(SearchResult {
-                                            session_id: session_id.clone(),
-                                            message_uuid: raw_msg.uuid.clone(),
-                                            snippet,
-                                            match_type: "tool_input".to_string(),
-                                        });
-                                    }
-                                }
-                                
-                                // Search in tool results (content field)
-                                if let Some(content) = &block.content {
-                                    if content.to_lowercase().contains(query_lower) {
-                                        let snippet = self.create_snippet(content, query_lower);
-                                        results.push(SearchResult {
-                                            session_id: session_id.clone(),
-                                            message_uuid: raw_msg.uuid.clone(),
-                                            snippet,
-                                            match_type: "tool_result".to_string(),
-                                        });
-                                    }
-                                }
-                                
-                                // Search in structured tool results
-                                if let Some(tool_use_result) = &block.tool_use_result {
-                                    let result_text = serde_json::to_string(tool_use_result).unwrap_or_default();
-                                    if result_text.to_lowercase().contains(query_lower) {
-                                        let snippet = self.create_snippet(&result_text, query_lower);
-                                        results.push(SearchResult {
-                                            session_id: session_id.clone(),
-                                            message_uuid: raw_msg.uuid.clone(),
-                                            snippet,
-                                            match_type: "tool_structured_result".to_string(),
-                                        });
-                                    }
-                                }
+                        ContentBlock::ToolResult { tool_use_id, content, tool_use_result, .. } => {
+                            let tool_name = tool_names.get(&tool_use_id);
+                            if let Some(content) = content {
+                                push("tool_result", content, tool_name);
                             }
+                            if let Some(tool_use_result) = &tool_use_result {
+                                push("tool_structured_result", serde_json::to_string(tool_use_result).unwrap_or_default(), tool_name);
+                            }
+                        }
+                        ContentBlock::ServerToolUse { id, name, input } => {
+                            tool_names.insert(id, name.clone());
+                            push("tool_name", name.clone(), Some(&name));
+                            push("tool_input", serde_json::to_string(&input).unwrap_or_default(), Some(&name));
                         }
+                        _ => {}
                     }
                 }
             }
         }
-        
-        Ok(results)
+
+        documents
     }
     
     fn extract_session_id_fast(&self, line: &str) -> Option<String> {
@@ -715,49 +1546,261 @@ impl ChatService {
         None
     }
 
-    fn create_snippet(&self, text: &str, query: &str) -> String {
-        let text_lower = text.to_lowercase();
-        if let Some(byte_pos) = text_lower.find(query) {
-            // Convert byte position to character position
+    fn create_snippet(&self, text: &str, match_span: Option<Range<usize>>) -> (String, Option<SnippetHighlight>) {
+        if let Some(match_span) = match_span {
+            // Convert byte positions to character positions
             let chars: Vec<char> = text.chars().collect();
-            let text_lower_chars: Vec<char> = text_lower.chars().collect();
-            
-            // Find character position of match
-            let mut char_pos = 0;
-            let mut current_byte_pos = 0;
-            for (i, ch) in text_lower_chars.iter().enumerate() {
-                if current_byte_pos == byte_pos {
-                    char_pos = i;
-                    break;
-                }
-                current_byte_pos += ch.len_utf8();
-            }
+            let char_pos = text[..match_span.start].chars().count();
+            let match_char_len = text[match_span.clone()].chars().count();
             
             // Calculate snippet boundaries in character positions
             let start_char = char_pos.saturating_sub(30);
-            let query_char_len = query.chars().count();
-            let end_char = (char_pos + query_char_len + 30).min(chars.len());
+            let end_char = (char_pos + match_char_len + 30).min(chars.len());
             
             // Extract snippet using character positions
             let snippet: String = chars[start_char..end_char].iter().collect();
-            
-            if start_char > 0 && end_char < chars.len() {
-                format!("...{}...", snippet)
-            } else if start_char > 0 {
-                format!("...{}", snippet)
-            } else if end_char < chars.len() {
-                format!("{}...", snippet)
-            } else {
-                snippet
-            }
+            let prefix = if start_char > 0 { "..." } else { "" };
+            let suffix = if end_char < chars.len() { "..." } else { "" };
+
+            // Locate the match inside the final snippet, after the leading ellipsis
+            let match_start = prefix.len() + chars[start_char..char_pos].iter().map(|c| c.len_utf8()).sum::<usize>();
+            let snippet = format!("{}{}{}", prefix, snippet, suffix);
+            let highlight = SnippetHighlight::new(&snippet, match_start..match_start + match_span.len());
+
+            (snippet, Some(highlight))
         } else {
-            text.chars().take(60).collect::<String>() + if text.chars().count() > 60 { "..." } else { "" }
+            let snippet = text.chars().take(60).collect::<String>() + if text.chars().count() > 60 { "..." } else { "" };
+            (snippet, None)
+        }
+    }
+
+    /// Token usage and cost per local day, across all projects or only the one
+    /// in `project_path`, oldest day first.
+    pub async fn get_daily_costs(&self, project_path: Option<&Path>) -> Result<Vec<DailyCost>> {
+        let days = self.usage_stats(project_path, UsageRange::default(), UsageGroupBy::Day).await?;
+        Ok(days
+            .into_iter()
+            .map(|day| DailyCost {
+                date: day.key,
+                usage: day.usage,
+                cost_usd: day.cost_usd,
+            })
+            .collect())
+    }
+
+    pub async fn get_usage_stats(&self, range: UsageRange, group_by: UsageGroupBy) -> Result<Vec<UsageStats>> {
+        self.usage_stats(None, range, group_by).await
+    }
+
+    async fn usage_stats(&self, project_path: Option<&Path>, range: UsageRange, group_by: UsageGroupBy) -> Result<Vec<UsageStats>> {
+        let mut rollup = UsageRollup::new(range, group_by)?;
+
+        for (project_dir, session_files) in self.project_session_files(project_path).await? {
+            let indexed_files = self.load_session_files(&session_files).await?;
+            // Name projects like get_all_projects does, by the cwd of their sessions
+            let project = indexed_files
+                .iter()
+                .find_map(|indexed| indexed.session.as_ref())
+                .map(|session| session.project_path.clone())
+                .unwrap_or_else(|| project_dir.to_string_lossy().to_string());
+
+            for indexed in &indexed_files {
+                let Some(session) = &indexed.session else {
+                    continue;
+                };
+                for record in &indexed.activity {
+                    let cost_usd = self.pricing.cost(&record.model, &record.usage);
+                    rollup.add(&session.id, &project, record, cost_usd);
+                }
+            }
+        }
+
+        Ok(rollup.finish())
+    }
+
+    /// Per-tool call counts, failure rates, output sizes and common inputs, across
+    /// all projects or only the one in `project_path`.
+    pub async fn get_tool_stats(&self, project_path: Option<&Path>) -> Result<Vec<ToolStats>> {
+        let mut collector = ToolStatsCollector::default();
+        for (_, session_files) in self.project_session_files(project_path).await? {
+            for file_path in session_files {
+                // Files that can't be read are skipped, like in the session listing
+                if let Ok(messages) = self.parse_messages_from_file(&file_path, false).await {
+                    collector.add_session(&messages);
+                }
+            }
+        }
+
+        Ok(collector.finish())
+    }
+
+    /// The files a session read, edited, wrote or created, sorted by path.
+    pub async fn get_session_files(&self, session_id: &str) -> Result<Vec<SessionFile>> {
+        let jsonl_path = self.find_session_file(session_id).await?;
+        let messages = self.parse_messages_from_file(&jsonl_path, false).await?;
+        let mut collector = FileActivityCollector::default();
+        collector.add_messages(&messages);
+        Ok(collector.finish())
+    }
+
+    /// Every session, across all projects, that read or changed the file at `path`,
+    /// most recently touched first.
+    pub async fn get_file_history(&self, path: &Path) -> Result<Vec<FileHistoryEntry>> {
+        // There is no directory a relative path could sensibly be taken from here
+        if path.is_relative() {
+            let message = format!("{} is not an absolute path", path.display());
+            return Err(CommandError::new(CommandErrorKind::InvalidInput, message).into());
+        }
+        // Normalized like the recorded paths are
+        let path = &FileActivityCollector::resolve(None, &path.to_string_lossy());
+        let Some(file_name) = path.file_name() else {
+            let message = format!("{} is not a file path", path.display());
+            return Err(CommandError::new(CommandErrorKind::InvalidInput, message).into());
+        };
+        // Sessions that never mention the file name can't have touched the file, which
+        // spares parsing most of them. Matched as it's written inside a JSON string
+        let encoded_name = serde_json::to_string(&file_name.to_string_lossy())?;
+        let needle = encoded_name.trim_matches('"');
+
+        let mut entries = Vec::new();
+        for (project_dir, session_files) in self.project_session_files(None).await? {
+            // Titles are resolved once per project, and only if a session matched
+            let mut project_sessions: Option<Vec<ChatSession>> = None;
+
+            for file_path in session_files {
+                // Files that can't be read are skipped, like in the session listing
+                let Ok(bytes) = fs::read(&file_path).await else {
+                    continue;
+                };
+                if !String::from_utf8_lossy(&bytes).contains(needle) {
+                    continue;
+                }
+                let Ok(messages) = self.parse_messages_from_file(&file_path, false).await else {
+                    continue;
+                };
+                let mut collector = FileActivityCollector::default();
+                collector.add_messages(&messages);
+                let Some(file) = collector.into_file(path) else {
+                    continue;
+                };
+
+                let stamp = FileStamp::from_metadata(&fs::metadata(&file_path).await?);
+                let Some(indexed_session) = self.load_session_file(&file_path, stamp).await.and_then(|indexed| indexed.session) else {
+                    continue;
+                };
+                if project_sessions.is_none() {
+                    project_sessions = Some(self.get_project_sessions(&project_dir).await?);
+                }
+                let session = project_sessions
+                    .iter()
+                    .flatten()
+                    .find(|session| session.id == indexed_session.id)
+                    .cloned()
+                    .unwrap_or(indexed_session);
+                entries.push(FileHistoryEntry { session, file });
+            }
+        }
+
+        if let Some(index) = &self.session_index {
+            index.save().await.ok();
         }
+        entries.sort_by(|a, b| b.file.last_touched.cmp(&a.file.last_touched));
+        Ok(entries)
+    }
+
+    pub fn get_price_table(&self) -> PriceTable {
+        self.pricing.table()
+    }
+
+    pub async fn set_price_table(&self, table: PriceTable) -> Result<()> {
+        self.pricing.set_table(table).await
     }
 
     pub async fn get_session_file_path(&self, session_id: &str) -> Result<String> {
         let file_path = self.find_session_file(session_id).await?;
         Ok(file_path.to_string_lossy().to_string())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-}
\ No newline at end of file
+    // A projects directory with one session whose assistant calls the Edit tool
+    fn write_tool_session(root: &Path) {
+        let project_dir = root.join("projects").join("-root-proj");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let lines = [
+            r#"{"type":"user","uuid":"u1","parentUuid":null,"sessionId":"s1","timestamp":"2025-01-01T00:00:00Z","cwd":"/root/proj","version":"1.0","message":{"role":"user","content":"Change the greeting"}}"#,
+            r#"{"type":"assistant","uuid":"a1","parentUuid":"u1","sessionId":"s1","timestamp":"2025-01-01T00:00:01Z","cwd":"/root/proj","version":"1.0","message":{"id":"m1","role":"assistant","model":"claude-sonnet-4","content":[{"type":"tool_use","id":"t1","name":"Edit","input":{"file_path":"/root/proj/a.rs","old_string":"hi","new_string":"hello"}}]}}"#,
+        ];
+        std::fs::write(project_dir.join("s1.jsonl"), lines.join("\n") + "\n").unwrap();
+    }
+
+    fn assert_tool_name_hit(results: &[SearchResult]) {
+        let tool_match = results
+            .iter()
+            .flat_map(|result| &result.matches)
+            .find(|search_match| search_match.match_type == "tool_name")
+            .expect("a tool_name match");
+        assert_eq!(tool_match.snippet, "Tool: Edit");
+        let highlight = tool_match.highlight.as_ref().unwrap();
+        assert_eq!(&tool_match.snippet[highlight.byte_start..highlight.byte_end], "Edit");
+    }
+
+    #[tokio::test]
+    async fn search_matching_a_tool_name() {
+        let root = std::env::temp_dir().join(format!("ccchats-test-{}", uuid::Uuid::new_v4()));
+        write_tool_session(&root);
+
+        // Scanning the session files
+        let service = ChatService::new().with_roots(vec![root.clone()]);
+        assert_tool_name_hit(&service.search_chats("edit", SearchMode::Text).await.unwrap());
+        assert_tool_name_hit(&service.search_chats("tool:edit", SearchMode::Text).await.unwrap());
+
+        // Through the full-text index
+        let service = ChatService::with_data_dir(&root.join("data")).with_roots(vec![root.clone()]);
+        assert!(service.uses_search_index(SearchMode::Text));
+        assert_tool_name_hit(&service.search_chats("edit", SearchMode::Text).await.unwrap());
+        assert_tool_name_hit(&service.search_chats("tool:edit", SearchMode::Text).await.unwrap());
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn paging_index_results_reaches_every_message() {
+        let root = std::env::temp_dir().join(format!("ccchats-test-{}", uuid::Uuid::new_v4()));
+        let project_dir = root.join("projects").join("-root-proj");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        // Each message matches in both its text and its tool input, so it is two indexed fields
+        let lines: Vec<String> = (0..12)
+            .map(|i| {
+                serde_json::json!({
+                    "type": "assistant", "uuid": format!("a{}", i), "parentUuid": null, "sessionId": "s1",
+                    "timestamp": format!("2025-01-01T00:00:{:02}Z", i), "cwd": "/root/proj", "version": "1.0",
+                    "message": {"id": format!("m{}", i), "role": "assistant", "model": "claude-sonnet-4", "content": [
+                        {"type": "text", "text": "looking for the needle"},
+                        {"type": "tool_use", "id": format!("t{}", i), "name": "Grep", "input": {"pattern": "needle"}},
+                    ]},
+                })
+                .to_string()
+            })
+            .collect();
+        std::fs::write(project_dir.join("s1.jsonl"), lines.join("\n") + "\n").unwrap();
+
+        let service = ChatService::with_data_dir(&root.join("data")).with_roots(vec![root.clone()]);
+        let mut messages = HashSet::new();
+        let mut cursor = None;
+        loop {
+            let page = service.search_chats_page("needle", SearchMode::Text, cursor.as_deref(), 5).await.unwrap();
+            messages.extend(page.results.into_iter().map(|result| result.message_uuid));
+            match page.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => break,
+            }
+        }
+        assert_eq!(messages.len(), 12);
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
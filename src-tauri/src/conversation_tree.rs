@@ -0,0 +1,102 @@
+use crate::types::RawJsonlMessage;
+use std::collections::HashMap;
+
+/// The tree formed by the `parentUuid` links of a session's messages. Editing or
+/// retrying a prompt in Claude Code starts a new branch from an earlier message,
+/// so one parent can have several children.
+///
+/// Nodes are referred to by their position in the slice the graph was built from,
+/// which is also the order the lines were written in.
+pub struct ConversationGraph<'a> {
+    messages: &'a [RawJsonlMessage],
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+}
+
+impl<'a> ConversationGraph<'a> {
+    pub fn new(messages: &'a [RawJsonlMessage]) -> Self {
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        for (i, message) in messages.iter().enumerate() {
+            // A uuid written twice keeps its first position
+            positions.entry(message.uuid.as_str()).or_insert(i);
+        }
+
+        let mut parents = vec![None; messages.len()];
+        let mut children = vec![Vec::new(); messages.len()];
+        for (i, message) in messages.iter().enumerate() {
+            // Parents that live in another file, e.g. the session this one continues,
+            // make the message a root
+            let parent = message
+                .parent_uuid
+                .as_deref()
+                .and_then(|uuid| positions.get(uuid).copied())
+                .filter(|&parent| parent < i);
+            if let Some(parent) = parent {
+                children[parent].push(i);
+            }
+            parents[i] = parent;
+        }
+
+        Self {
+            messages,
+            parents,
+            children,
+        }
+    }
+
+    pub fn parent(&self, node: usize) -> Option<usize> {
+        self.parents[node]
+    }
+
+    pub fn children(&self, node: usize) -> &[usize] {
+        &self.children[node]
+    }
+
+    pub fn roots(&self) -> Vec<usize> {
+        (0..self.messages.len()).filter(|&i| self.parents[i].is_none()).collect()
+    }
+
+    pub fn leaves(&self) -> Vec<usize> {
+        (0..self.messages.len()).filter(|&i| self.children[i].is_empty()).collect()
+    }
+
+    pub fn find(&self, uuid: &str) -> Option<usize> {
+        self.messages.iter().position(|message| message.uuid == uuid)
+    }
+
+    /// The leaf of the branch the session currently continues from. When a summary
+    /// points into this session, the branch it describes wins; otherwise it's the
+    /// branch that was written to last.
+    pub fn active_leaf(&self, summaries: &HashMap<String, String>) -> Option<usize> {
+        let summarized = (0..self.messages.len())
+            .rev()
+            .find(|&i| summaries.contains_key(&self.messages[i].uuid));
+
+        match summarized {
+            // The conversation may have been continued after the summary was written
+            Some(node) => Some(self.latest_leaf_under(node)),
+            None => self.leaves().last().copied(),
+        }
+    }
+
+    fn latest_leaf_under(&self, node: usize) -> usize {
+        self.leaves()
+            .into_iter()
+            .rev()
+            .find(|&leaf| self.path_to(leaf).contains(&node))
+            .unwrap_or(node)
+    }
+
+    /// Nodes from the root down to `leaf`, inclusive.
+    pub fn path_to(&self, leaf: usize) -> Vec<usize> {
+        let mut path = vec![leaf];
+        let mut node = leaf;
+        // Parents always come earlier in the file, so this can't loop
+        while let Some(parent) = self.parents[node] {
+            path.push(parent);
+            node = parent;
+        }
+        path.reverse();
+        path
+    }
+}
@@ -0,0 +1,131 @@
+use crate::types::{CommandError, CommandErrorKind, DataRoot};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tokio::fs;
+
+/// Claude Code's own data directory: `$CLAUDE_CONFIG_DIR` when set, otherwise
+/// `~/.claude`. None when there is no home directory to look in.
+pub fn default_root() -> Option<PathBuf> {
+    match std::env::var_os("CLAUDE_CONFIG_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => dirs::home_dir().map(|home| home.join(".claude")),
+    }
+}
+
+/// The directory holding the project folders of a root. A root may be given as a
+/// Claude config directory or directly as its `projects` directory, which is
+/// recognized by its `-encoded-path` project folders. Anything else resolves to
+/// `<root>/projects`, so a config directory without sessions reads as missing
+/// instead of listing its other folders as projects.
+pub fn projects_dir(root: &Path) -> PathBuf {
+    let projects = root.join("projects");
+    if !projects.is_dir() && is_projects_dir(root) {
+        return root.to_path_buf();
+    }
+    projects
+}
+
+fn is_projects_dir(dir: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        entry.file_type().is_ok_and(|file_type| file_type.is_dir()) && entry.file_name().to_string_lossy().starts_with('-')
+    })
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct DataRootSettings {
+    roots: Vec<PathBuf>,
+}
+
+/// The directories sessions are read from, optionally persisted as JSON so the
+/// list survives restarts. An empty list means the default root.
+pub struct DataRoots {
+    path: Option<PathBuf>,
+    roots: RwLock<Vec<PathBuf>>,
+}
+
+impl DataRoots {
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        Self {
+            path: None,
+            roots: RwLock::new(roots),
+        }
+    }
+
+    pub fn load(path: PathBuf) -> Self {
+        // An unreadable file falls back to the default root rather than failing startup
+        let settings: DataRootSettings = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        Self {
+            path: Some(path),
+            roots: RwLock::new(settings.roots),
+        }
+    }
+
+    pub fn roots(&self) -> Vec<PathBuf> {
+        let roots = self.roots.read().unwrap();
+        if roots.is_empty() {
+            return default_root().into_iter().collect();
+        }
+        roots.clone()
+    }
+
+    /// The projects directories of the roots that currently exist, so that e.g. a
+    /// backup drive that isn't mounted is left out instead of failing everything.
+    pub fn projects_dirs(&self) -> Vec<PathBuf> {
+        self.roots()
+            .iter()
+            .map(|root| projects_dir(root))
+            .filter(|dir| dir.is_dir())
+            .collect()
+    }
+
+    /// Like `projects_dirs`, but an error naming the searched locations when none exist.
+    pub fn require_projects_dirs(&self) -> Result<Vec<PathBuf>> {
+        let dirs = self.projects_dirs();
+        if dirs.is_empty() {
+            let roots = self.roots();
+            let Some(first_root) = roots.first() else {
+                let message = "No Claude Code data directory configured and no home directory to look in";
+                return Err(CommandError::new(CommandErrorKind::DataRootMissing, message).into());
+            };
+            let searched: Vec<String> = roots.iter().map(|root| root.display().to_string()).collect();
+            let message = format!("No Claude Code data found in {}", searched.join(", "));
+            return Err(CommandError::new(CommandErrorKind::DataRootMissing, message).with_path(first_root).into());
+        }
+        Ok(dirs)
+    }
+
+    pub fn status(&self) -> Vec<DataRoot> {
+        self.roots()
+            .iter()
+            .map(|root| {
+                let projects_path = projects_dir(root);
+                DataRoot {
+                    path: root.to_string_lossy().to_string(),
+                    available: projects_path.is_dir(),
+                    projects_path: projects_path.to_string_lossy().to_string(),
+                }
+            })
+            .collect()
+    }
+
+    pub async fn set_roots(&self, roots: Vec<PathBuf>) -> Result<()> {
+        if let Some(path) = &self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            let settings = DataRootSettings { roots: roots.clone() };
+            fs::write(path, serde_json::to_vec_pretty(&settings)?).await?;
+        }
+        *self.roots.write().unwrap() = roots;
+        Ok(())
+    }
+}
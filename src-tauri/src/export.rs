@@ -0,0 +1,456 @@
+use crate::types::{ChatMessage, ChatSession, ContentBlock, MessageContent};
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd};
+use std::fmt::Write;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+
+/// Renders a session as Markdown: YAML front matter, one heading per message,
+/// fenced tool inputs and results, and thinking folded into `<details>`.
+pub fn to_markdown(session: &ChatSession, messages: &[ChatMessage]) -> String {
+    let mut out = String::new();
+
+    let mut models: Vec<&str> = Vec::new();
+    for model in messages.iter().filter_map(|message| message.model.as_deref()) {
+        if !models.contains(&model) && !model.starts_with('<') {
+            models.push(model);
+        }
+    }
+
+    // JSON strings are valid YAML scalars, which saves escaping by hand
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+    out.push_str("---\n");
+    let _ = writeln!(out, "title: {}", quote(&session.title));
+    let _ = writeln!(out, "session_id: {}", quote(&session.id));
+    let _ = writeln!(out, "project: {}", quote(&session.project_path));
+    let _ = writeln!(
+        out,
+        "models: [{}]",
+        models.iter().map(|model| quote(model)).collect::<Vec<_>>().join(", ")
+    );
+    let _ = writeln!(out, "started: {}", quote(&session.timestamp));
+    let _ = writeln!(out, "last_updated: {}", quote(&session.last_updated));
+    let _ = writeln!(out, "messages: {}", session.message_count);
+    out.push_str("---\n\n");
+
+    let _ = writeln!(out, "# {}\n", session.title);
+    write_messages(&mut out, messages, 2);
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+fn write_messages(out: &mut String, messages: &[ChatMessage], heading_level: usize) {
+    let hashes = "#".repeat(heading_level);
+
+    for message in messages {
+        let role = match message.message_type.as_str() {
+            "assistant" => "Assistant",
+            "system" => "System",
+            _ => "User",
+        };
+        match message.model.as_deref().filter(|model| !model.starts_with('<')) {
+            Some(model) => {
+                let _ = writeln!(out, "{} {} ({}) · {}\n", hashes, role, model, message.timestamp);
+            }
+            None => {
+                let _ = writeln!(out, "{} {} · {}\n", hashes, role, message.timestamp);
+            }
+        }
+
+        match &message.content {
+            MessageContent::Text(text) => {
+                let _ = writeln!(out, "{}\n", text.trim_end());
+            }
+            MessageContent::Mixed(blocks) => {
+                for block in blocks {
+                    write_block(out, block, heading_level);
+                }
+            }
+        }
+    }
+}
+
+fn write_block(out: &mut String, block: &ContentBlock, heading_level: usize) {
+    match block {
+        ContentBlock::Text { text } => {
+            let _ = writeln!(out, "{}\n", text.trim_end());
+        }
+        ContentBlock::Thinking { thinking, .. } => {
+            let _ = writeln!(out, "<details>\n<summary>Thinking</summary>\n\n{}\n\n</details>\n", thinking.trim_end());
+        }
+        ContentBlock::ToolUse { name, input, content, is_error, sidechain, .. } => {
+            let _ = writeln!(out, "**Tool: {}**\n", name);
+            write_tool_input(out, name, input);
+            write_tool_result(out, content.as_deref(), *is_error);
+
+            if let Some(sidechain) = sidechain {
+                out.push_str("<details>\n<summary>Sub-agent transcript</summary>\n\n");
+                write_messages(out, sidechain, heading_level + 1);
+                out.push_str("</details>\n\n");
+            }
+        }
+        ContentBlock::ToolResult { content, is_error, .. } => write_tool_result(out, content.as_deref(), *is_error),
+        _ => {}
+    }
+}
+
+fn write_tool_input(out: &mut String, tool_name: &str, input: &serde_json::Value) {
+    // Show shell commands as shell code rather than as a JSON string
+    if tool_name == "Bash" {
+        if let Some(command) = input.get("command").and_then(|v| v.as_str()) {
+            write_fenced(out, "bash", command);
+            return;
+        }
+    }
+    let json = serde_json::to_string_pretty(input).unwrap_or_default();
+    write_fenced(out, "json", &json);
+}
+
+fn write_tool_result(out: &mut String, content: Option<&str>, is_error: Option<bool>) {
+    let Some(content) = content else {
+        return;
+    };
+    let label = if is_error == Some(true) { "Error" } else { "Result" };
+    let _ = writeln!(out, "{}:\n", label);
+    write_fenced(out, "", content);
+}
+
+// Uses a fence longer than any run of backticks inside the code
+fn write_fenced(out: &mut String, language: &str, code: &str) {
+    let longest_run = code
+        .split(|c| c != '`')
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let _ = writeln!(out, "{}{}\n{}\n{}\n", fence, language, code.trim_end_matches('\n'), fence);
+}
+
+const HTML_STYLE: &str = r#"
+* { box-sizing: border-box; }
+body { margin: 0; background: #f8fafc; color: #0f172a; font: 15px/1.6 -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif; }
+main { max-width: 920px; margin: 0 auto; padding: 32px 20px 64px; }
+h1 { font-size: 1.6em; margin: 0 0 12px; }
+.meta { display: grid; grid-template-columns: max-content 1fr; gap: 2px 16px; margin: 0 0 32px; color: #475569; font-size: 0.9em; }
+.meta dt { font-weight: 600; }
+.meta dd { margin: 0; overflow-wrap: anywhere; }
+.message { background: #fff; border: 1px solid #e2e8f0; border-radius: 8px; padding: 14px 18px; margin: 0 0 16px; }
+.message.user { border-left: 4px solid #3b82f6; }
+.message.assistant { border-left: 4px solid #a855f7; }
+.message.system { border-left: 4px solid #94a3b8; background: #f8fafc; font-size: 0.9em; }
+.message-header { display: flex; gap: 10px; align-items: baseline; margin-bottom: 8px; font-size: 0.85em; color: #64748b; }
+.message-header .role { font-weight: 600; color: #0f172a; }
+.message-header time { margin-left: auto; }
+.message-body > :first-child { margin-top: 0; }
+.message-body > :last-child { margin-bottom: 0; }
+pre { padding: 10px 12px; border-radius: 6px; overflow-x: auto; font: 13px/1.45 ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; background: #f6f8fa; border: 1px solid #e2e8f0; }
+code { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; font-size: 0.9em; }
+:not(pre) > code { background: #f1f5f9; padding: 1px 4px; border-radius: 4px; }
+details { border: 1px solid #e2e8f0; border-radius: 6px; margin: 10px 0; background: #f8fafc; }
+details > summary { cursor: pointer; padding: 6px 10px; font-weight: 600; font-size: 0.9em; }
+details > .details-body { padding: 0 12px 10px; }
+details.thinking { background: #faf5ff; border-color: #e9d5ff; }
+details.thinking .details-body { color: #6b21a8; font-style: italic; }
+details.tool { background: #eff6ff; border-color: #bfdbfe; }
+details.sidechain { background: #fff; }
+.label { font-size: 0.8em; font-weight: 600; color: #475569; margin: 8px 0 2px; text-transform: uppercase; letter-spacing: 0.04em; }
+.label.error { color: #dc2626; }
+pre.error { border-color: #fca5a5; background: #fef2f2; }
+.todos { list-style: none; padding: 0; margin: 8px 0; }
+.todos li { border-left: 3px solid #94a3b8; background: #f8fafc; padding: 4px 10px; margin: 4px 0; border-radius: 4px; }
+.todos li.completed { border-color: #22c55e; background: #f0fdf4; text-decoration: line-through; color: #64748b; }
+.todos li.in_progress { border-color: #3b82f6; background: #eff6ff; }
+.todos .status { display: inline-block; width: 1.4em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #e2e8f0; padding: 4px 8px; }
+"#;
+
+/// Renders sessions as standalone HTML pages with inline styles and code
+/// highlighted ahead of time, so they open in any browser without the app.
+pub struct HtmlRenderer {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl HtmlRenderer {
+    // Loading the bundled syntaxes is slow, so reuse one renderer for many sessions
+    pub fn new() -> Self {
+        let mut themes = ThemeSet::load_defaults();
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.themes.remove("InspiredGitHub").unwrap_or_default(),
+        }
+    }
+
+    pub fn render(&self, session: &ChatSession, messages: &[ChatMessage]) -> String {
+        let mut out = String::new();
+        let title = escape_html(&session.title);
+
+        let _ = write!(
+            out,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+             <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<main>\n",
+            title, HTML_STYLE
+        );
+
+        let mut models: Vec<&str> = Vec::new();
+        for model in messages.iter().filter_map(|message| message.model.as_deref()) {
+            if !models.contains(&model) && !model.starts_with('<') {
+                models.push(model);
+            }
+        }
+
+        let _ = writeln!(out, "<header>\n<h1>{}</h1>\n<dl class=\"meta\">", title);
+        for (label, value) in [
+            ("Project", session.project_path.clone()),
+            ("Session", session.id.clone()),
+            ("Models", models.join(", ")),
+            ("Started", session.timestamp.clone()),
+            ("Last updated", session.last_updated.clone()),
+        ] {
+            let _ = writeln!(out, "<dt>{}</dt><dd>{}</dd>", label, escape_html(&value));
+        }
+        out.push_str("</dl>\n</header>\n");
+
+        self.write_messages(&mut out, messages);
+        out.push_str("</main>\n</body>\n</html>\n");
+        out
+    }
+
+    fn write_messages(&self, out: &mut String, messages: &[ChatMessage]) {
+        for message in messages {
+            let (class, role) = match message.message_type.as_str() {
+                "assistant" => ("assistant", "Assistant"),
+                "system" => ("system", "System"),
+                _ => ("user", "User"),
+            };
+            let _ = write!(
+                out,
+                "<article class=\"message {}\">\n<div class=\"message-header\"><span class=\"role\">{}</span>",
+                class, role
+            );
+            if let Some(model) = message.model.as_deref().filter(|model| !model.starts_with('<')) {
+                let _ = write!(out, "<span class=\"model\">{}</span>", escape_html(model));
+            }
+            let _ = writeln!(
+                out,
+                "<time datetime=\"{0}\">{0}</time></div>\n<div class=\"message-body\">",
+                escape_html(&message.timestamp)
+            );
+
+            match &message.content {
+                MessageContent::Text(text) => self.write_markdown(out, text),
+                MessageContent::Mixed(blocks) => {
+                    for block in blocks {
+                        self.write_block(out, block);
+                    }
+                }
+            }
+
+            out.push_str("</div>\n</article>\n");
+        }
+    }
+
+    fn write_block(&self, out: &mut String, block: &ContentBlock) {
+        match block {
+            ContentBlock::Text { text } => self.write_markdown(out, text),
+            ContentBlock::Thinking { thinking, .. } => {
+                out.push_str("<details class=\"thinking\"><summary>Thinking</summary><div class=\"details-body\">\n");
+                self.write_markdown(out, thinking);
+                out.push_str("</div></details>\n");
+            }
+            ContentBlock::ToolUse { name, input, content, is_error, tool_use_result, sidechain, .. } => {
+                let _ = writeln!(
+                    out,
+                    "<details class=\"tool\"><summary>Tool: {}</summary><div class=\"details-body\">",
+                    escape_html(name)
+                );
+
+                match (name.as_str(), input.get("todos")) {
+                    ("TodoWrite", Some(todos)) => self.write_todos(out, todos),
+                    _ => {
+                        out.push_str("<div class=\"label\">Input</div>\n");
+                        self.write_tool_input(out, name, input);
+                        self.write_tool_result(out, content.as_deref(), *is_error, tool_use_result.as_ref());
+                    }
+                }
+
+                if let Some(sidechain) = sidechain {
+                    out.push_str("<details class=\"sidechain\"><summary>Sub-agent transcript</summary><div class=\"details-body\">\n");
+                    self.write_messages(out, sidechain);
+                    out.push_str("</div></details>\n");
+                }
+                out.push_str("</div></details>\n");
+            }
+            ContentBlock::ToolResult { content, is_error, tool_use_result, .. } => {
+                out.push_str("<details class=\"tool\"><summary>Tool result</summary><div class=\"details-body\">\n");
+                self.write_tool_result(out, content.as_deref(), *is_error, tool_use_result.as_ref());
+                out.push_str("</div></details>\n");
+            }
+            _ => {}
+        }
+    }
+
+    fn write_tool_input(&self, out: &mut String, tool_name: &str, input: &serde_json::Value) {
+        if tool_name == "Bash" {
+            if let Some(command) = input.get("command").and_then(|v| v.as_str()) {
+                self.write_code(out, "bash", command);
+                return;
+            }
+        }
+        self.write_code(out, "json", &serde_json::to_string_pretty(input).unwrap_or_default());
+    }
+
+    fn write_tool_result(
+        &self,
+        out: &mut String,
+        content: Option<&str>,
+        is_error: Option<bool>,
+        structured: Option<&serde_json::Value>,
+    ) {
+        if let Some(content) = content {
+            if is_error == Some(true) {
+                let _ = writeln!(out, "<div class=\"label error\">Error</div>\n<pre class=\"error\">{}</pre>", escape_html(content));
+            } else {
+                let _ = writeln!(out, "<div class=\"label\">Result</div>\n<pre>{}</pre>", escape_html(content));
+            }
+        }
+        if let Some(structured) = structured {
+            out.push_str("<details><summary>Structured result</summary><div class=\"details-body\">\n");
+            self.write_code(out, "json", &serde_json::to_string_pretty(structured).unwrap_or_default());
+            out.push_str("</div></details>\n");
+        }
+    }
+
+    fn write_todos(&self, out: &mut String, todos: &serde_json::Value) {
+        out.push_str("<ul class=\"todos\">\n");
+        for todo in todos.as_array().into_iter().flatten() {
+            let status = todo.get("status").and_then(|v| v.as_str()).unwrap_or("pending");
+            let icon = match status {
+                "completed" => "✓",
+                "in_progress" => "◐",
+                _ => "○",
+            };
+            let content = todo.get("content").and_then(|v| v.as_str()).unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "<li class=\"{}\"><span class=\"status\">{}</span>{}</li>",
+                escape_html(status),
+                icon,
+                escape_html(content)
+            );
+        }
+        out.push_str("</ul>\n");
+    }
+
+    fn write_code(&self, out: &mut String, language: &str, code: &str) {
+        let syntax = self
+            .syntaxes
+            .find_syntax_by_token(language)
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text());
+        match highlighted_html_for_string(code, &self.syntaxes, syntax, &self.theme) {
+            Ok(html) => out.push_str(&html),
+            Err(_) => {
+                let _ = writeln!(out, "<pre>{}</pre>", escape_html(code));
+            }
+        }
+    }
+
+    // Message text is Markdown. Raw HTML in it is shown as text, never interpreted,
+    // and fenced code blocks are highlighted like tool inputs. Only web and mail links
+    // are kept, others (javascript:, data:, relative paths) are left as their text, and
+    // images become links to them so that opening the export fetches nothing
+    fn write_markdown(&self, out: &mut String, text: &str) {
+        let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+        let mut events = Vec::new();
+        let mut code_block: Option<(String, String)> = None;
+        // Whether each open link was kept
+        let mut links: Vec<bool> = Vec::new();
+        // Each open image, with the event its link starts at when it was kept
+        let mut images: Vec<Option<(usize, CowStr)>> = Vec::new();
+
+        for event in Parser::new_ext(text, options) {
+            match event {
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    let language = match kind {
+                        CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or_default().to_string(),
+                        CodeBlockKind::Indented => String::new(),
+                    };
+                    code_block = Some((language, String::new()));
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    if let Some((language, code)) = code_block.take() {
+                        let mut html = String::new();
+                        self.write_code(&mut html, &language, &code);
+                        events.push(Event::Html(html.into()));
+                    }
+                }
+                Event::Text(code) if code_block.is_some() => {
+                    if let Some((_, block)) = code_block.as_mut() {
+                        block.push_str(&code);
+                    }
+                }
+                Event::Html(html) | Event::InlineHtml(html) => events.push(Event::Text(html)),
+                Event::Start(Tag::Link { link_type, dest_url, title, id }) => {
+                    let kept = is_safe_link(link_type, &dest_url);
+                    if kept {
+                        events.push(Event::Start(Tag::Link { link_type, dest_url, title, id }));
+                    }
+                    links.push(kept);
+                }
+                Event::End(TagEnd::Link) => {
+                    if links.pop() == Some(true) {
+                        events.push(Event::End(TagEnd::Link));
+                    }
+                }
+                // An image inside a kept link is left as its alt text, links can't nest
+                Event::Start(Tag::Image { link_type, dest_url, title, id }) => {
+                    if is_safe_link(link_type, &dest_url) && !links.contains(&true) {
+                        images.push(Some((events.len(), dest_url.clone())));
+                        events.push(Event::Start(Tag::Link { link_type, dest_url, title, id }));
+                    } else {
+                        images.push(None);
+                    }
+                }
+                Event::End(TagEnd::Image) => {
+                    if let Some(Some((start, dest_url))) = images.pop() {
+                        // Without alt text the link would have nothing to click on
+                        if events.len() == start + 1 {
+                            events.push(Event::Text(dest_url));
+                        }
+                        events.push(Event::End(TagEnd::Link));
+                    }
+                }
+                event => events.push(event),
+            }
+        }
+
+        pulldown_cmark::html::push_html(out, events.into_iter());
+    }
+}
+
+fn is_safe_link(link_type: LinkType, dest_url: &str) -> bool {
+    // Email autolinks are written without their mailto: scheme
+    if link_type == LinkType::Email {
+        return true;
+    }
+    let dest_url = dest_url.to_ascii_lowercase();
+    ["http://", "https://", "mailto:"].iter().any(|scheme| dest_url.starts_with(scheme))
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,118 @@
+use crate::types::{ChatMessage, ContentBlock, FileEdit, MessageContent, SessionFile};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Collects the files a session read or changed from the inputs of its tool calls.
+#[derive(Default)]
+pub struct FileActivityCollector {
+    files: HashMap<PathBuf, SessionFile>,
+}
+
+impl FileActivityCollector {
+    pub fn add_messages(&mut self, messages: &[ChatMessage]) {
+        for message in messages {
+            let MessageContent::Mixed(blocks) = &message.content else {
+                continue;
+            };
+            for block in blocks {
+                let ContentBlock::ToolUse { name, input, is_error, tool_use_result, diff, sidechain, .. } = block else {
+                    continue;
+                };
+                // Files touched by sub-agents count too
+                if let Some(sidechain) = sidechain {
+                    self.add_messages(sidechain);
+                }
+                // A call that failed, e.g. an Edit whose old_string wasn't found, left the file alone
+                if *is_error == Some(true) {
+                    continue;
+                }
+
+                let path_field = match name.as_str() {
+                    "Read" | "Edit" | "MultiEdit" | "Write" => "file_path",
+                    "NotebookEdit" => "notebook_path",
+                    "Glob" => "path",
+                    _ => continue,
+                };
+                // Glob searches the working directory unless given a path
+                let path = match input.get(path_field).and_then(|v| v.as_str()) {
+                    Some(path) => Self::resolve(message.cwd.as_deref(), path),
+                    None if name == "Glob" => match &message.cwd {
+                        Some(cwd) => Self::resolve(None, cwd),
+                        None => continue,
+                    },
+                    None => continue,
+                };
+
+                let file = self.files.entry(path.clone()).or_insert_with(|| SessionFile {
+                    path: path.to_string_lossy().to_string(),
+                    reads: 0,
+                    edits: 0,
+                    writes: 0,
+                    globs: 0,
+                    created: false,
+                    first_touched: message.timestamp.clone(),
+                    last_touched: message.timestamp.clone(),
+                    final_edit: None,
+                });
+                file.last_touched = message.timestamp.clone();
+
+                match name.as_str() {
+                    "Read" => file.reads += 1,
+                    "Glob" => file.globs += 1,
+                    _ => {
+                        if name == "Write" {
+                            file.writes += 1;
+                            let kind = tool_use_result.as_ref().and_then(|result| result.get("type")).and_then(|v| v.as_str());
+                            file.created |= kind == Some("create");
+                        } else {
+                            file.edits += 1;
+                        }
+                        file.final_edit = Some(FileEdit {
+                            tool_name: name.clone(),
+                            message_uuid: message.uuid.clone(),
+                            timestamp: message.timestamp.clone(),
+                            diff: diff.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Resolves a relative path against `cwd` and normalizes it lexically, dropping
+    /// `.` and trailing separators and applying `..`, so that paths written
+    /// differently compare equal. Used both for tool inputs and for lookups.
+    pub fn resolve(cwd: Option<&str>, path: &str) -> PathBuf {
+        let path = match cwd {
+            Some(cwd) if Path::new(path).is_relative() => Path::new(cwd).join(path),
+            _ => PathBuf::from(path),
+        };
+
+        let mut normalized = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                // Symlinks aren't followed, the recorded paths are just as written
+                Component::ParentDir if normalized.file_name().is_some() => {
+                    normalized.pop();
+                }
+                // Above the root there's nowhere further up to go
+                Component::ParentDir if normalized.has_root() => {}
+                component => normalized.push(component),
+            }
+        }
+        normalized
+    }
+
+    /// The activity on one file, None when the session didn't touch it.
+    pub fn into_file(mut self, path: &Path) -> Option<SessionFile> {
+        self.files.remove(path)
+    }
+
+    /// Every file touched, sorted by path.
+    pub fn finish(self) -> Vec<SessionFile> {
+        let mut files: Vec<SessionFile> = self.files.into_values().collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        files
+    }
+}
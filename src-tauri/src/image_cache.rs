@@ -0,0 +1,95 @@
+use crate::types::ImageRef;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// The URI scheme images are served to the webview over, as `chatimage://localhost/<id>`.
+pub const IMAGE_PROTOCOL: &str = "chatimage";
+
+/// Decoded session images, stored on disk under the SHA-256 of their content so the
+/// same screenshot is written once however many sessions or parses it appears in.
+/// Without a directory images are still referenced but not kept.
+pub struct ImageCache {
+    dir: Option<PathBuf>,
+}
+
+impl ImageCache {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self { dir }
+    }
+
+    /// Decodes an API image source (`{"type": "base64", "media_type", "data"}`) into
+    /// the cache. None for sources that aren't inline base64 images, e.g. URLs.
+    pub fn store(&self, source: &serde_json::Value) -> Option<ImageRef> {
+        if source.get("type").and_then(|v| v.as_str()) != Some("base64") {
+            return None;
+        }
+        let media_type = source.get("media_type").and_then(|v| v.as_str())?;
+        let extension = Self::extension(media_type)?;
+        let data = source.get("data").and_then(|v| v.as_str())?;
+        let bytes = base64::engine::general_purpose::STANDARD.decode(data).ok()?;
+
+        let hash: String = Sha256::digest(&bytes).iter().map(|byte| format!("{:02x}", byte)).collect();
+        let id = format!("{}.{}", hash, extension);
+
+        if let Some(dir) = &self.dir {
+            let path = dir.join(&id);
+            if !path.exists() {
+                // Written under a temporary name so a half-written file is never served
+                let partial = dir.join(format!("{}.part", id));
+                let written = std::fs::create_dir_all(dir)
+                    .and_then(|_| std::fs::write(&partial, &bytes))
+                    .and_then(|_| std::fs::rename(&partial, &path));
+                if written.is_err() {
+                    return None;
+                }
+            }
+        }
+
+        let size = imagesize::blob_size(&bytes).ok();
+        Some(ImageRef {
+            id,
+            media_type: media_type.to_string(),
+            width: size.and_then(|size| u32::try_from(size.width).ok()),
+            height: size.and_then(|size| u32::try_from(size.height).ok()),
+            byte_size: bytes.len() as u64,
+        })
+    }
+
+    /// Whether stored images are written to disk, so their inline data can be dropped.
+    pub fn is_persistent(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// The bytes and media type of a cached image. Ids are checked to be exactly
+    /// what `store` produces, so a request can't reach outside the cache directory.
+    pub fn load(&self, id: &str) -> Option<(Vec<u8>, &'static str)> {
+        let (hash, extension) = id.split_once('.')?;
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let media_type = Self::media_type(extension)?;
+        let bytes = std::fs::read(self.dir.as_ref()?.join(id)).ok()?;
+        Some((bytes, media_type))
+    }
+
+    fn extension(media_type: &str) -> Option<&'static str> {
+        match media_type {
+            "image/png" => Some("png"),
+            "image/jpeg" => Some("jpg"),
+            "image/gif" => Some("gif"),
+            "image/webp" => Some("webp"),
+            _ => None,
+        }
+    }
+
+    fn media_type(extension: &str) -> Option<&'static str> {
+        match extension {
+            "png" => Some("image/png"),
+            "jpg" => Some("image/jpeg"),
+            "gif" => Some("image/gif"),
+            "webp" => Some("image/webp"),
+            _ => None,
+        }
+    }
+}
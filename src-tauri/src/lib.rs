@@ -1,32 +1,231 @@
-mod types;
-mod chat_service;
+pub mod types;
+mod archive;
+pub mod chat_service;
+mod conversation_tree;
+mod data_roots;
+mod export;
+mod file_activity;
+mod image_cache;
+mod pricing;
+mod search_index;
+mod search_query;
+mod session_index;
+mod session_watcher;
+mod tool_diff;
+mod tool_stats;
+mod usage_stats;
 
 use types::*;
 use chat_service::ChatService;
+use image_cache::IMAGE_PROTOCOL;
+use pricing::PriceTable;
+use session_watcher::SessionWatcher;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::http::{header, Response, StatusCode};
+use tauri::{AppHandle, Emitter, Manager, State};
 
 // Chat viewer commands
 #[tauri::command]
-async fn get_all_projects() -> Result<Vec<ProjectFolder>, String> {
-    let service = ChatService::new();
-    service.get_all_projects().await.map_err(|e| e.to_string())
+async fn get_all_projects(service: State<'_, ChatService>) -> Result<Vec<ProjectFolder>, CommandError> {
+    service.get_all_projects().await.map_err(CommandError::from)
 }
 
 #[tauri::command]
-async fn get_chat_messages(session_id: String) -> Result<Vec<ChatMessage>, String> {
-    let service = ChatService::new();
-    service.get_chat_messages(&session_id).await.map_err(|e| e.to_string())
+async fn get_chat_messages(service: State<'_, ChatService>, session_id: String) -> Result<Vec<ChatMessage>, CommandError> {
+    service.get_chat_messages(&session_id).await.map_err(CommandError::from)
 }
 
+// Messages appended since `offset`, for following a session while it's written
 #[tauri::command]
-async fn search_chats(query: String) -> Result<Vec<SearchResult>, String> {
-    let service = ChatService::new();
-    service.search_chats(&query).await.map_err(|e| e.to_string())
+async fn read_session_tail(service: State<'_, ChatService>, session_id: String, offset: u64) -> Result<SessionTail, CommandError> {
+    service.read_session_tail(&session_id, offset).await.map_err(CommandError::from)
 }
 
 #[tauri::command]
-async fn get_session_file_path(session_id: String) -> Result<String, String> {
-    let service = ChatService::new();
-    service.get_session_file_path(&session_id).await.map_err(|e| e.to_string())
+async fn get_conversation_tree(service: State<'_, ChatService>, session_id: String) -> Result<ConversationTree, CommandError> {
+    service.get_conversation_tree(&session_id).await.map_err(CommandError::from)
+}
+
+// Messages along one branch of the tree, the active one when no leaf is given
+#[tauri::command]
+async fn get_branch_messages(
+    service: State<'_, ChatService>,
+    session_id: String,
+    leaf_uuid: Option<String>,
+) -> Result<Vec<ChatMessage>, CommandError> {
+    service
+        .get_branch_messages(&session_id, leaf_uuid.as_deref())
+        .await
+        .map_err(CommandError::from)
+}
+
+#[tauri::command]
+async fn export_session(
+    service: State<'_, ChatService>,
+    session_id: String,
+    format: Option<ExportFormat>,
+) -> Result<String, CommandError> {
+    service
+        .export_session(&session_id, format.unwrap_or_default())
+        .await
+        .map_err(CommandError::from)
+}
+
+#[tauri::command]
+async fn export_archive(
+    service: State<'_, ChatService>,
+    request: ArchiveRequest,
+    destination: String,
+) -> Result<ArchiveSummary, CommandError> {
+    service
+        .export_archive(request, std::path::Path::new(&destination))
+        .await
+        .map_err(CommandError::from)
+}
+
+#[tauri::command]
+async fn search_chats(
+    service: State<'_, ChatService>,
+    query: String,
+    mode: Option<SearchMode>,
+) -> Result<Vec<SearchResult>, SearchError> {
+    service.search_chats(&query, mode.unwrap_or_default()).await
+}
+
+#[tauri::command]
+async fn search_chats_page(
+    service: State<'_, ChatService>,
+    query: String,
+    mode: Option<SearchMode>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<SearchPage, SearchError> {
+    service
+        .search_chats_page(&query, mode.unwrap_or_default(), cursor.as_deref(), limit.unwrap_or(50))
+        .await
+}
+
+// Emits "search-results" events while searching; resolves to false if cancelled
+#[tauri::command]
+async fn search_chats_stream(
+    app: AppHandle,
+    service: State<'_, ChatService>,
+    search_id: u64,
+    query: String,
+    mode: Option<SearchMode>,
+) -> Result<bool, SearchError> {
+    service
+        .search_chats_streaming(search_id, &query, mode.unwrap_or_default(), |results| {
+            app.emit("search-results", SearchBatch { search_id, results }).ok();
+        })
+        .await
+}
+
+#[tauri::command]
+fn cancel_search(service: State<'_, ChatService>, search_id: u64) {
+    service.cancel_search(search_id);
+}
+
+// Per-day token usage and cost, for one project directory or all of them
+#[tauri::command]
+async fn get_daily_costs(service: State<'_, ChatService>, project_path: Option<String>) -> Result<Vec<DailyCost>, CommandError> {
+    service
+        .get_daily_costs(project_path.as_deref().map(std::path::Path::new))
+        .await
+        .map_err(CommandError::from)
+}
+
+#[tauri::command]
+async fn get_usage_stats(
+    service: State<'_, ChatService>,
+    range: Option<UsageRange>,
+    group_by: UsageGroupBy,
+) -> Result<Vec<UsageStats>, CommandError> {
+    service
+        .get_usage_stats(range.unwrap_or_default(), group_by)
+        .await
+        .map_err(CommandError::from)
+}
+
+// Per-tool statistics, for one project directory or all of them
+#[tauri::command]
+async fn get_tool_stats(service: State<'_, ChatService>, project_path: Option<String>) -> Result<Vec<ToolStats>, CommandError> {
+    service
+        .get_tool_stats(project_path.as_deref().map(std::path::Path::new))
+        .await
+        .map_err(CommandError::from)
+}
+
+// Files a session read, edited, wrote or created
+#[tauri::command]
+async fn get_session_files(service: State<'_, ChatService>, session_id: String) -> Result<Vec<SessionFile>, CommandError> {
+    service.get_session_files(&session_id).await.map_err(CommandError::from)
+}
+
+// Sessions in any project that touched the file at `path`
+#[tauri::command]
+async fn get_file_history(service: State<'_, ChatService>, path: String) -> Result<Vec<FileHistoryEntry>, CommandError> {
+    service
+        .get_file_history(std::path::Path::new(&path))
+        .await
+        .map_err(CommandError::from)
+}
+
+#[tauri::command]
+async fn get_parse_diagnostics(
+    service: State<'_, ChatService>,
+    project_path: Option<String>,
+) -> Result<Vec<ParseDiagnostics>, CommandError> {
+    service
+        .get_parse_diagnostics(project_path.as_deref().map(std::path::Path::new))
+        .await
+        .map_err(CommandError::from)
+}
+
+#[tauri::command]
+fn get_price_table(service: State<'_, ChatService>) -> PriceTable {
+    service.get_price_table()
+}
+
+#[tauri::command]
+async fn set_price_table(service: State<'_, ChatService>, table: PriceTable) -> Result<(), CommandError> {
+    service.set_price_table(table).await.map_err(CommandError::from)
+}
+
+#[tauri::command]
+async fn get_session_file_path(service: State<'_, ChatService>, session_id: String) -> Result<String, CommandError> {
+    service.get_session_file_path(&session_id).await.map_err(CommandError::from)
+}
+
+#[tauri::command]
+fn get_data_roots(service: State<'_, ChatService>) -> Vec<DataRoot> {
+    service.get_data_roots()
+}
+
+#[tauri::command]
+async fn set_data_roots(
+    app: AppHandle,
+    service: State<'_, ChatService>,
+    watcher: State<'_, Mutex<Option<SessionWatcher>>>,
+    roots: Vec<String>,
+) -> Result<Vec<DataRoot>, CommandError> {
+    let roots = service
+        .set_data_roots(roots.into_iter().map(PathBuf::from).collect())
+        .await
+        .map_err(CommandError::from)?;
+    // Replacing the watcher stops the old one, which watched the previous roots
+    *watcher.lock().unwrap() = watch_sessions(&app, &service);
+    Ok(roots)
+}
+
+// Emits "session-file-changed" events; without a watcher the viewer just doesn't live-update
+fn watch_sessions(app: &AppHandle, service: &ChatService) -> Option<SessionWatcher> {
+    let handle = app.clone();
+    SessionWatcher::start(&service.projects_dirs(), move |event| {
+        handle.emit("session-file-changed", event).ok();
+    })
+    .ok()
 }
 
 // Legacy greet command for compatibility
@@ -39,12 +238,54 @@ fn greet(name: &str) -> String {
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        // Session images, loaded by the webview as chatimage://localhost/<id>
+        .register_uri_scheme_protocol(IMAGE_PROTOCOL, |ctx, request| {
+            let id = request.uri().path().trim_start_matches('/');
+            let image = ctx.app_handle().try_state::<ChatService>().and_then(|service| service.read_image(id));
+            match image {
+                Some((bytes, media_type)) => Response::builder()
+                    .header(header::CONTENT_TYPE, media_type)
+                    // Ids are content hashes, so an image never changes
+                    .header(header::CACHE_CONTROL, "max-age=31536000, immutable")
+                    .body(bytes)
+                    .unwrap(),
+                None => Response::builder().status(StatusCode::NOT_FOUND).body(Vec::new()).unwrap(),
+            }
+        })
+        .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            let cache_dir = app.path().app_cache_dir()?;
+            let service = ChatService::with_data_dir(&data_dir).with_image_cache(cache_dir.join("images"));
+
+            app.manage(Mutex::new(watch_sessions(app.handle(), &service)));
+
+            app.manage(service);
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             greet,
             get_all_projects,
             get_chat_messages,
+            read_session_tail,
+            get_conversation_tree,
+            get_branch_messages,
+            export_session,
+            export_archive,
             search_chats,
-            get_session_file_path
+            search_chats_page,
+            search_chats_stream,
+            cancel_search,
+            get_daily_costs,
+            get_usage_stats,
+            get_tool_stats,
+            get_parse_diagnostics,
+            get_session_files,
+            get_file_history,
+            get_price_table,
+            set_price_table,
+            get_session_file_path,
+            get_data_roots,
+            set_data_roots
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,118 @@
+use crate::types::TokenUsage;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tokio::fs;
+
+/// Dollar prices per million tokens for every model whose name contains `pattern`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub pattern: String,
+    pub input: f64,
+    pub output: f64,
+    pub cache_write: f64,
+    pub cache_read: f64,
+}
+
+impl ModelPrice {
+    fn new(pattern: &str, input: f64, output: f64, cache_write: f64, cache_read: f64) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            input,
+            output,
+            cache_write,
+            cache_read,
+        }
+    }
+
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.input_tokens as f64 * self.input
+            + usage.output_tokens as f64 * self.output
+            + usage.cache_creation_input_tokens as f64 * self.cache_write
+            + usage.cache_read_input_tokens as f64 * self.cache_read)
+            / 1_000_000.0
+    }
+}
+
+/// Ordered list of model prices, the first entry whose pattern matches wins.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceTable {
+    pub models: Vec<ModelPrice>,
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        // Anthropic list prices; more specific patterns have to come first
+        Self {
+            models: vec![
+                ModelPrice::new("opus-4-5", 5.0, 25.0, 6.25, 0.5),
+                ModelPrice::new("opus", 15.0, 75.0, 18.75, 1.5),
+                ModelPrice::new("sonnet", 3.0, 15.0, 3.75, 0.3),
+                ModelPrice::new("haiku-4-5", 1.0, 5.0, 1.25, 0.1),
+                ModelPrice::new("haiku", 0.8, 4.0, 1.0, 0.08),
+            ],
+        }
+    }
+}
+
+impl PriceTable {
+    pub fn price_for(&self, model: &str) -> Option<&ModelPrice> {
+        let model = model.to_lowercase();
+        self.models
+            .iter()
+            .find(|price| model.contains(&price.pattern.to_lowercase()))
+    }
+
+    /// Cost in dollars, zero for models without a price (e.g. `<synthetic>`).
+    pub fn cost(&self, model: &str, usage: &TokenUsage) -> f64 {
+        self.price_for(model).map(|price| price.cost(usage)).unwrap_or(0.0)
+    }
+}
+
+/// The price table in use, optionally persisted as JSON so edits survive restarts.
+pub struct Pricing {
+    path: Option<PathBuf>,
+    table: RwLock<PriceTable>,
+}
+
+impl Pricing {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            table: RwLock::new(PriceTable::default()),
+        }
+    }
+
+    pub fn load(path: PathBuf) -> Self {
+        // An unreadable file falls back to the defaults rather than failing startup
+        let table = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+
+        Self {
+            path: Some(path),
+            table: RwLock::new(table),
+        }
+    }
+
+    pub fn table(&self) -> PriceTable {
+        self.table.read().unwrap().clone()
+    }
+
+    pub fn cost(&self, model: &str, usage: &TokenUsage) -> f64 {
+        self.table.read().unwrap().cost(model, usage)
+    }
+
+    pub async fn set_table(&self, table: PriceTable) -> Result<()> {
+        if let Some(path) = &self.path {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(path, serde_json::to_vec_pretty(&table)?).await?;
+        }
+        *self.table.write().unwrap() = table;
+        Ok(())
+    }
+}
@@ -0,0 +1,352 @@
+use crate::search_query::{fuzzy_distance, Filter, SearchQuery, TextTerm};
+use crate::session_index::FileStamp;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{AllQuery, BooleanQuery, FuzzyTermQuery, Occur, PhraseQuery, Query, RangeQuery, RegexQuery, TermQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, FAST, INDEXED, STORED, STRING, TEXT,
+};
+use tantivy::tokenizer::{LowerCaser, RawTokenizer, TextAnalyzer};
+use tantivy::{doc, Index, IndexReader, IndexWriter, TantivyDocument, Term};
+
+// Bump whenever the schema or the way documents are extracted changes,
+// which forces a full rebuild of the index on the next search
+const SCHEMA_VERSION: u32 = 3;
+const WRITER_HEAP_SIZE: usize = 50_000_000;
+// Indexes a whole value as one lowercased token, for case-insensitive exact filters
+const RAW_LOWERCASE_TOKENIZER: &str = "raw_lowercase";
+
+/// One searchable field of a message, e.g. a text block or a tool input.
+#[derive(Debug, Clone)]
+pub struct SearchDocument {
+    pub session_id: String,
+    pub message_uuid: String,
+    pub match_type: String, // "content", "thinking", "tool_name", "tool_input", "tool_result", "tool_structured_result"
+    pub text: String,
+    pub tool_name: Option<String>, // Set for tool_* documents when the tool call is known
+    pub role: String, // "user" or "assistant"
+    pub model: Option<String>,
+    pub project_path: String,
+    pub timestamp_ms: i64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    files: HashMap<String, FileStamp>,
+}
+
+struct SearchFields {
+    file_path: Field,
+    session_id: Field,
+    message_uuid: Field,
+    match_type: Field,
+    body: Field,
+    tool_name: Field,
+    role: Field,
+    model: Field,
+    project_path: Field,
+    timestamp: Field,
+}
+
+/// Tantivy-backed inverted index over every searchable field of every session.
+/// Files are re-indexed incrementally based on their `FileStamp`.
+pub struct SearchIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: SearchFields,
+    manifest_path: PathBuf,
+    manifest: Mutex<Manifest>,
+}
+
+impl SearchIndex {
+    pub fn open(index_dir: &Path) -> Result<Self> {
+        let manifest_path = index_dir.join("manifest.json");
+        let manifest = std::fs::read(&manifest_path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Manifest>(&bytes).ok())
+            .filter(|manifest| manifest.version == SCHEMA_VERSION);
+
+        // Without a matching manifest the existing segments can't be trusted, start over
+        let manifest = match manifest {
+            Some(manifest) => manifest,
+            None => {
+                if index_dir.exists() {
+                    std::fs::remove_dir_all(index_dir)?;
+                }
+                Manifest {
+                    version: SCHEMA_VERSION,
+                    files: HashMap::new(),
+                }
+            }
+        };
+        std::fs::create_dir_all(index_dir)?;
+
+        let (schema, fields) = Self::build_schema();
+        let index = Index::open_or_create(MmapDirectory::open(index_dir)?, schema)?;
+        index.tokenizers().register(
+            RAW_LOWERCASE_TOKENIZER,
+            TextAnalyzer::builder(RawTokenizer::default()).filter(LowerCaser).build(),
+        );
+        let reader = index.reader()?;
+        let writer = index.writer(WRITER_HEAP_SIZE)?;
+
+        Ok(Self {
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            fields,
+            manifest_path,
+            manifest: Mutex::new(manifest),
+        })
+    }
+
+    fn build_schema() -> (Schema, SearchFields) {
+        let mut builder = Schema::builder();
+        let filter_options = TextOptions::default().set_stored().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(RAW_LOWERCASE_TOKENIZER)
+                .set_index_option(IndexRecordOption::Basic),
+        );
+        let fields = SearchFields {
+            file_path: builder.add_text_field("file_path", STRING),
+            session_id: builder.add_text_field("session_id", STRING | STORED),
+            message_uuid: builder.add_text_field("message_uuid", STORED),
+            match_type: builder.add_text_field("match_type", STRING | STORED),
+            body: builder.add_text_field("body", TEXT | STORED),
+            tool_name: builder.add_text_field("tool_name", filter_options.clone()),
+            role: builder.add_text_field("role", filter_options.clone()),
+            model: builder.add_text_field("model", filter_options.clone()),
+            project_path: builder.add_text_field("project_path", filter_options),
+            timestamp: builder.add_i64_field("timestamp", INDEXED | FAST | STORED),
+        };
+        (builder.build(), fields)
+    }
+
+    /// Returns the files whose stamp differs from the one they were indexed with.
+    pub fn changed_files(&self, current: &[(PathBuf, FileStamp)]) -> Vec<(PathBuf, FileStamp)> {
+        let manifest = self.manifest.lock().unwrap();
+        current
+            .iter()
+            .filter(|(path, stamp)| manifest.files.get(path.to_string_lossy().as_ref()) != Some(stamp))
+            .cloned()
+            .collect()
+    }
+
+    /// Removes the documents of every indexed file that is not in `current`.
+    /// Returns whether anything was removed.
+    pub fn remove_missing_files(&self, current: &[(PathBuf, FileStamp)]) -> bool {
+        let current: HashSet<String> = current
+            .iter()
+            .map(|(path, _)| path.to_string_lossy().to_string())
+            .collect();
+        let writer = self.writer.lock().unwrap();
+        let mut manifest = self.manifest.lock().unwrap();
+        let before = manifest.files.len();
+
+        manifest.files.retain(|path, _| {
+            if current.contains(path) {
+                return true;
+            }
+            writer.delete_term(Term::from_field_text(self.fields.file_path, path));
+            false
+        });
+
+        manifest.files.len() != before
+    }
+
+    /// Replaces all documents of `file_path` with `documents`.
+    pub fn index_file(&self, file_path: &Path, stamp: FileStamp, documents: Vec<SearchDocument>) -> Result<()> {
+        let path = file_path.to_string_lossy().to_string();
+        let writer = self.writer.lock().unwrap();
+
+        writer.delete_term(Term::from_field_text(self.fields.file_path, &path));
+        for document in documents {
+            let mut tantivy_doc = doc!(
+                self.fields.file_path => path.as_str(),
+                self.fields.session_id => document.session_id,
+                self.fields.message_uuid => document.message_uuid,
+                self.fields.match_type => document.match_type,
+                self.fields.body => document.text,
+                self.fields.role => document.role,
+                self.fields.project_path => document.project_path,
+                self.fields.timestamp => document.timestamp_ms,
+            );
+            if let Some(tool_name) = document.tool_name {
+                tantivy_doc.add_text(self.fields.tool_name, tool_name);
+            }
+            if let Some(model) = document.model {
+                tantivy_doc.add_text(self.fields.model, model);
+            }
+            writer.add_document(tantivy_doc)?;
+        }
+
+        self.manifest.lock().unwrap().files.insert(path, stamp);
+        Ok(())
+    }
+
+    /// Makes pending changes visible to searches and persists the manifest.
+    pub fn commit(&self) -> Result<()> {
+        self.writer.lock().unwrap().commit()?;
+        self.reader.reload()?;
+
+        // The manifest is written after the commit so a crash in between only
+        // causes files to be re-indexed, never to go missing
+        let bytes = serde_json::to_vec(&*self.manifest.lock().unwrap())?;
+        let tmp_path = self.manifest_path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, bytes)?;
+        std::fs::rename(&tmp_path, &self.manifest_path)?;
+        Ok(())
+    }
+
+    /// Runs `query` against the indexed bodies and returns the matching documents
+    /// ordered by BM25 score, skipping the first `offset`. With `fuzzy`, single
+    /// words tolerate typos.
+    pub fn search(&self, query: &SearchQuery, fuzzy: bool, offset: usize, limit: usize) -> Result<Vec<SearchDocument>> {
+        let searcher = self.reader.searcher();
+        let query = self.build_query(query, fuzzy)?;
+
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit).and_offset(offset))?;
+        let mut documents = Vec::with_capacity(top_docs.len());
+
+        for (_score, doc_address) in top_docs {
+            let doc: TantivyDocument = searcher.doc(doc_address)?;
+            let get_text = |field: Field| {
+                doc.get_first(field)
+                    .and_then(|value| value.as_str())
+                    .map(|value| value.to_string())
+            };
+
+            documents.push(SearchDocument {
+                session_id: get_text(self.fields.session_id).unwrap_or_default(),
+                message_uuid: get_text(self.fields.message_uuid).unwrap_or_default(),
+                match_type: get_text(self.fields.match_type).unwrap_or_default(),
+                text: get_text(self.fields.body).unwrap_or_default(),
+                tool_name: get_text(self.fields.tool_name),
+                role: get_text(self.fields.role).unwrap_or_default(),
+                model: get_text(self.fields.model),
+                project_path: get_text(self.fields.project_path).unwrap_or_default(),
+                timestamp_ms: doc
+                    .get_first(self.fields.timestamp)
+                    .and_then(|value| value.as_i64())
+                    .unwrap_or_default(),
+            });
+        }
+
+        Ok(documents)
+    }
+
+    fn build_query(&self, query: &SearchQuery, fuzzy: bool) -> Result<Box<dyn Query>> {
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+
+        let groups = query
+            .groups
+            .iter()
+            .map(|group| {
+                let mut terms = group
+                    .iter()
+                    .filter_map(|term| self.text_query(term, fuzzy).transpose())
+                    .map(|term_query| term_query.map(|q| (Occur::Must, q)))
+                    .collect::<Result<Vec<_>>>()?;
+                if terms.is_empty() {
+                    // Only punctuation was typed, which the tokenizer drops entirely
+                    terms.push((Occur::Must, Box::new(AllQuery)));
+                }
+                Ok((Occur::Should, Box::new(BooleanQuery::new(terms)) as Box<dyn Query>))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if groups.is_empty() {
+            // Tantivy needs at least one positive clause for exclusions to apply to
+            clauses.push((Occur::Must, Box::new(AllQuery)));
+        } else {
+            clauses.push((Occur::Must, Box::new(BooleanQuery::new(groups))));
+        }
+
+        for term in &query.excluded {
+            // Exclusions stay exact, a typo-tolerant exclusion would drop too much
+            if let Some(term_query) = self.text_query(term, false)? {
+                clauses.push((Occur::MustNot, term_query));
+            }
+        }
+
+        // Filters of the same kind are alternatives, so each kind becomes one SHOULD group
+        let mut filter_kinds: Vec<Vec<&Filter>> = Vec::new();
+        for filter in &query.filters {
+            match filter_kinds.iter_mut().find(|kind| kind[0].same_kind(filter)) {
+                Some(kind) => kind.push(filter),
+                None => filter_kinds.push(vec![filter]),
+            }
+        }
+        for kind in filter_kinds {
+            let alternatives = kind
+                .into_iter()
+                .map(|filter| Ok((Occur::Should, self.filter_query(filter)?)))
+                .collect::<Result<Vec<_>>>()?;
+            clauses.push((Occur::Must, Box::new(BooleanQuery::new(alternatives))));
+        }
+        for filter in &query.excluded_filters {
+            clauses.push((Occur::MustNot, self.filter_query(filter)?));
+        }
+
+        Ok(Box::new(BooleanQuery::new(clauses)))
+    }
+
+    // Runs the term through the body tokenizer so it matches what was indexed;
+    // anything that splits into several tokens (e.g. "main.rs") becomes a phrase
+    fn text_query(&self, term: &TextTerm, fuzzy: bool) -> Result<Option<Box<dyn Query>>> {
+        let mut tokenizer = self.index.tokenizer_for_field(self.fields.body)?;
+        let mut stream = tokenizer.token_stream(term.text());
+        let mut terms = Vec::new();
+        while stream.advance() {
+            terms.push(Term::from_field_text(self.fields.body, &stream.token().text));
+        }
+
+        Ok(match terms.len() {
+            0 => None,
+            1 if fuzzy && matches!(term, TextTerm::Word(_)) => {
+                let distance = fuzzy_distance(term.text());
+                Some(Box::new(FuzzyTermQuery::new(terms.remove(0), distance, true)))
+            }
+            1 => Some(Box::new(TermQuery::new(terms.remove(0), IndexRecordOption::WithFreqs))),
+            _ => Some(Box::new(PhraseQuery::new(terms))),
+        })
+    }
+
+    fn filter_query(&self, filter: &Filter) -> Result<Box<dyn Query>> {
+        let exact = |field: Field, value: &str| -> Box<dyn Query> {
+            Box::new(TermQuery::new(Term::from_field_text(field, value), IndexRecordOption::Basic))
+        };
+        let contains = |field: Field, value: &str| -> Result<Box<dyn Query>> {
+            let pattern = format!(".*{}.*", regex::escape(value));
+            Ok(Box::new(RegexQuery::from_pattern(&pattern, field)?))
+        };
+
+        Ok(match filter {
+            Filter::Tool(tool) => exact(self.fields.tool_name, tool),
+            Filter::Model(model) => contains(self.fields.model, model)?,
+            Filter::Project(project) => contains(self.fields.project_path, project)?,
+            Filter::MatchType(match_types) => Box::new(BooleanQuery::new(
+                match_types
+                    .iter()
+                    .map(|match_type| (Occur::Should, exact(self.fields.match_type, match_type)))
+                    .collect(),
+            )),
+            Filter::Role(role) => exact(self.fields.role, role),
+            Filter::After(after) => Box::new(RangeQuery::new(
+                Bound::Included(Term::from_field_i64(self.fields.timestamp, *after)),
+                Bound::Unbounded,
+            )),
+            Filter::Before(before) => Box::new(RangeQuery::new(
+                Bound::Unbounded,
+                Bound::Excluded(Term::from_field_i64(self.fields.timestamp, *before)),
+            )),
+        })
+    }
+}
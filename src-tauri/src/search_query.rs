@@ -0,0 +1,362 @@
+use crate::search_index::SearchDocument;
+use crate::types::{SearchError, SearchMode};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate};
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+/// A piece of free text the document body has to contain.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextTerm {
+    Word(String),
+    Phrase(String),
+}
+
+impl TextTerm {
+    pub fn text(&self) -> &str {
+        match self {
+            TextTerm::Word(text) | TextTerm::Phrase(text) => text,
+        }
+    }
+}
+
+/// A `key:value` restriction on the metadata of a document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Tool(String),
+    Model(String),
+    Project(String),
+    MatchType(Vec<String>),
+    Role(String),
+    After(i64),  // Unix milliseconds, inclusive
+    Before(i64), // Unix milliseconds, exclusive
+}
+
+impl Filter {
+    fn parse(key: &str, value: &str) -> Result<Option<Filter>> {
+        let filter = match key {
+            "tool" => Filter::Tool(value.to_lowercase()),
+            "model" => Filter::Model(value.to_lowercase()),
+            "project" => Filter::Project(value.to_lowercase()),
+            "type" => Filter::MatchType(Self::match_types_for(&value.to_lowercase())),
+            "role" => Filter::Role(value.to_lowercase()),
+            "after" => Filter::After(parse_date(key, value)?),
+            "before" => Filter::Before(parse_date(key, value)?),
+            _ => return Ok(None),
+        };
+        Ok(Some(filter))
+    }
+
+    // Accepts the match types verbatim plus a few shorter aliases
+    fn match_types_for(value: &str) -> Vec<String> {
+        let match_types: &[&str] = match value {
+            "text" | "content" => &["content"],
+            "tool" => &["tool_name", "tool_input", "tool_result", "tool_structured_result"],
+            "input" => &["tool_input"],
+            "result" => &["tool_result", "tool_structured_result"],
+            other => return vec![other.to_string()],
+        };
+        match_types.iter().map(|s| s.to_string()).collect()
+    }
+
+    /// Filters of the same kind are OR'ed together, different kinds are AND'ed.
+    pub fn same_kind(&self, other: &Filter) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    pub fn matches(&self, document: &SearchDocument) -> bool {
+        let contains = |value: &Option<String>, needle: &str| {
+            value.as_ref().is_some_and(|v| v.to_lowercase().contains(needle))
+        };
+
+        match self {
+            Filter::Tool(tool) => document.tool_name.as_ref().is_some_and(|name| name.to_lowercase() == *tool),
+            Filter::Model(model) => contains(&document.model, model),
+            Filter::Project(project) => document.project_path.to_lowercase().contains(project.as_str()),
+            Filter::MatchType(match_types) => match_types.contains(&document.match_type),
+            Filter::Role(role) => document.role == *role,
+            Filter::After(after) => document.timestamp_ms >= *after,
+            Filter::Before(before) => document.timestamp_ms < *before,
+        }
+    }
+}
+
+fn parse_date(key: &str, value: &str) -> Result<i64> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp_millis());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|date| date.timestamp_millis())
+        .map_err(|_| anyhow!("Invalid date for {}: \"{}\", expected YYYY-MM-DD", key, value))
+}
+
+/// Parsed form of the search box syntax:
+///
+/// - `foo bar` / `foo AND bar`: both words must appear
+/// - `foo OR bar`: either side may match
+/// - `"exact phrase"`: words must appear in this order
+/// - `-word`, `-"phrase"`, `-tool:Bash`: exclude matches
+/// - `tool:`, `model:`, `project:`, `type:`, `role:`, `after:`, `before:` filters
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    // Alternatives separated by OR, each a list of terms that must all match
+    pub groups: Vec<Vec<TextTerm>>,
+    pub excluded: Vec<TextTerm>,
+    pub filters: Vec<Filter>,
+    pub excluded_filters: Vec<Filter>,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut query = SearchQuery::default();
+        let mut group = Vec::new();
+
+        for token in split_tokens(input) {
+            let (negated, token) = match token.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest),
+                _ => (false, token.as_str()),
+            };
+
+            if !negated && token == "OR" {
+                if !group.is_empty() {
+                    query.groups.push(std::mem::take(&mut group));
+                }
+                continue;
+            }
+            if !negated && token == "AND" {
+                continue;
+            }
+
+            if let Some((key, value)) = token.split_once(':') {
+                let value = value.trim_matches('"');
+                if !value.is_empty() {
+                    if let Some(filter) = Filter::parse(&key.to_lowercase(), value)? {
+                        if negated {
+                            query.excluded_filters.push(filter);
+                        } else {
+                            query.filters.push(filter);
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            let term = if token.len() > 1 && token.starts_with('"') {
+                TextTerm::Phrase(token.trim_matches('"').to_lowercase())
+            } else {
+                TextTerm::Word(token.trim_matches('"').to_lowercase())
+            };
+            if term.text().is_empty() {
+                continue;
+            }
+
+            if negated {
+                query.excluded.push(term);
+            } else {
+                group.push(term);
+            }
+        }
+
+        if !group.is_empty() {
+            query.groups.push(group);
+        }
+
+        Ok(query)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty() && self.excluded.is_empty() && self.filters.is_empty() && self.excluded_filters.is_empty()
+    }
+
+    /// The terms worth highlighting, in the order they were typed.
+    pub fn positive_terms(&self) -> impl Iterator<Item = &TextTerm> {
+        self.groups.iter().flatten()
+    }
+
+    /// Cheap pre-check on a raw, lowercased JSONL line: false means no document
+    /// from this line can match, so it doesn't need to be parsed.
+    pub fn could_match_line(&self, line_lower: &str) -> bool {
+        self.groups.is_empty()
+            || self.groups.iter().any(|group| group.iter().all(|term| line_lower.contains(term.text())))
+    }
+
+    pub fn matches(&self, document: &SearchDocument) -> bool {
+        self.matches_with(document, |text, term| text.contains(term.text()))
+    }
+
+    /// Like `matches`, but decides whether a single term occurs in the lowercased
+    /// document text with `term_matches`.
+    pub fn matches_with(&self, document: &SearchDocument, term_matches: impl Fn(&str, &TextTerm) -> bool) -> bool {
+        let text = document.text.to_lowercase();
+
+        let text_matches = self.groups.is_empty()
+            || self.groups.iter().any(|group| group.iter().all(|term| term_matches(&text, term)));
+        // Exclusions stay exact, a typo-tolerant exclusion would drop too much
+        let text_excluded = self.excluded.iter().any(|term| text.contains(term.text()));
+
+        text_matches
+            && !text_excluded
+            && self.filters.iter().all(|filter| {
+                // Satisfied if any filter of the same kind matches
+                self.filters
+                    .iter()
+                    .filter(|other| other.same_kind(filter))
+                    .any(|other| other.matches(document))
+            })
+            && !self.excluded_filters.iter().any(|filter| filter.matches(document))
+    }
+}
+
+// Splits on whitespace while keeping quoted sections (including `key:"a b"`) together
+fn split_tokens(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for ch in input.chars() {
+        if ch == '"' {
+            in_quotes = !in_quotes;
+            current.push(ch);
+        } else if ch.is_whitespace() && !in_quotes {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(ch);
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+/// Maximum edit distance a fuzzy search tolerates for `term`. Short terms must
+/// match exactly, otherwise almost everything would match them.
+pub fn fuzzy_distance(term: &str) -> u8 {
+    match term.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+/// Decides which documents match a query in a given `SearchMode` and where
+/// inside a document's text the match is.
+pub struct SearchMatcher {
+    pub mode: SearchMode,
+    // Empty in regex mode, where the whole input is the pattern
+    pub query: SearchQuery,
+    // Regex mode: the user's pattern. Otherwise one case-insensitive literal per term,
+    // used to locate the match for the snippet
+    patterns: Vec<Regex>,
+}
+
+impl SearchMatcher {
+    pub fn new(input: &str, mode: SearchMode) -> Result<Self, SearchError> {
+        if mode == SearchMode::Regex {
+            // A blank pattern would match everything, it's treated as no query like in the other modes
+            if input.trim().is_empty() {
+                return Ok(Self {
+                    mode,
+                    query: SearchQuery::default(),
+                    patterns: Vec::new(),
+                });
+            }
+            let regex = RegexBuilder::new(input)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| SearchError::InvalidRegex {
+                    pattern: input.to_string(),
+                    message: e.to_string(),
+                })?;
+            return Ok(Self {
+                mode,
+                query: SearchQuery::default(),
+                patterns: vec![regex],
+            });
+        }
+
+        let query = SearchQuery::parse(input).map_err(|e| SearchError::InvalidQuery { message: e.to_string() })?;
+        let patterns = query
+            .positive_terms()
+            .filter_map(|term| {
+                RegexBuilder::new(&regex::escape(term.text()))
+                    .case_insensitive(true)
+                    .build()
+                    .ok()
+            })
+            .collect();
+
+        Ok(Self { mode, query, patterns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self.mode {
+            SearchMode::Regex => self.patterns.is_empty(),
+            SearchMode::Text | SearchMode::Fuzzy => self.query.is_empty(),
+        }
+    }
+
+    /// Cheap pre-check on a raw JSONL line, see `SearchQuery::could_match_line`.
+    pub fn could_match_line(&self, line_lower: &str) -> bool {
+        match self.mode {
+            SearchMode::Text => self.query.could_match_line(line_lower),
+            // JSON escaping and typos make the raw line useless for ruling anything out
+            SearchMode::Regex | SearchMode::Fuzzy => true,
+        }
+    }
+
+    pub fn matches(&self, document: &SearchDocument) -> bool {
+        match self.mode {
+            SearchMode::Text => self.query.matches(document),
+            SearchMode::Regex => self.patterns.first().is_some_and(|pattern| pattern.is_match(&document.text)),
+            SearchMode::Fuzzy => self.query.matches_with(document, |text, term| {
+                text.contains(term.text()) || Self::fuzzy_find(text, term).is_some()
+            }),
+        }
+    }
+
+    /// Byte range of the first match inside `text`, if it can be located.
+    pub fn find_span(&self, text: &str) -> Option<Range<usize>> {
+        if let Some(span) = self.patterns.iter().find_map(|pattern| pattern.find(text)) {
+            return Some(span.range());
+        }
+
+        if self.mode == SearchMode::Fuzzy {
+            // Lowercasing can change byte lengths, so only trust the span if it didn't
+            let text_lower = text.to_lowercase();
+            if text_lower.len() == text.len() {
+                return self.query.positive_terms().find_map(|term| Self::fuzzy_find(&text_lower, term));
+            }
+        }
+
+        None
+    }
+
+    // Finds the first word of `text` within the fuzzy distance of a single-word term
+    fn fuzzy_find(text: &str, term: &TextTerm) -> Option<Range<usize>> {
+        let TextTerm::Word(word) = term else {
+            return None;
+        };
+        let max_distance = fuzzy_distance(word) as usize;
+
+        let mut start = None;
+        for (i, ch) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+            match (start, ch.is_alphanumeric() || ch == '_') {
+                (None, true) => start = Some(i),
+                (Some(word_start), false) => {
+                    if strsim::osa_distance(&text[word_start..i], word) <= max_distance {
+                        return Some(word_start..i);
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+}
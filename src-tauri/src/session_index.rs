@@ -0,0 +1,162 @@
+use crate::types::{ChatSession, ParseIssue};
+use crate::usage_stats::ActivityRecord;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tokio::fs;
+
+// Bump whenever the cached entry format changes so stale caches are discarded
+const INDEX_VERSION: u32 = 5;
+
+/// Identifies one version of a file on disk. A cached entry is reused only
+/// while both the modification time and the size are unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub modified_ms: u64,
+    pub size: u64,
+}
+
+impl FileStamp {
+    pub fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        let modified_ms = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+
+        Self {
+            modified_ms,
+            size: metadata.len(),
+        }
+    }
+}
+
+/// Everything `get_project_sessions` needs from a single JSONL file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedSessionFile {
+    pub stamp: FileStamp,
+    // None when the file has no readable user message
+    pub session: Option<ChatSession>,
+    pub last_message_uuid: String,
+    // leafUuid -> summary, for summary lines written into this file
+    pub summaries: HashMap<String, String>,
+    // Costs are derived from the token usage on demand
+    pub activity: Vec<ActivityRecord>,
+    // The first skipped lines, issue_count has how many there were in total
+    pub issues: Vec<ParseIssue>,
+    pub issue_count: usize,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct IndexFile {
+    version: u32,
+    files: HashMap<String, IndexedSessionFile>,
+}
+
+/// On-disk cache of parsed session metadata keyed by JSONL file path.
+pub struct SessionIndex {
+    path: PathBuf,
+    files: Mutex<HashMap<String, IndexedSessionFile>>,
+    dirty: AtomicBool,
+}
+
+impl SessionIndex {
+    pub fn load(path: PathBuf) -> Self {
+        // A missing, corrupt or outdated cache just means everything gets re-parsed
+        let files = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<IndexFile>(&bytes).ok())
+            .filter(|index| index.version == INDEX_VERSION)
+            .map(|index| index.files)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            files: Mutex::new(files),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    pub fn get(&self, file_path: &Path, stamp: FileStamp) -> Option<IndexedSessionFile> {
+        let files = self.files.lock().unwrap();
+        files
+            .get(file_path.to_string_lossy().as_ref())
+            .filter(|entry| entry.stamp == stamp)
+            .cloned()
+    }
+
+    pub fn insert(&self, file_path: &Path, entry: IndexedSessionFile) {
+        let mut files = self.files.lock().unwrap();
+        files.insert(file_path.to_string_lossy().to_string(), entry);
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub fn find_session_file(&self, session_id: &str) -> Option<PathBuf> {
+        let files = self.files.lock().unwrap();
+        files
+            .iter()
+            .find(|(_, entry)| entry.session.as_ref().is_some_and(|s| s.id == session_id))
+            .map(|(path, _)| PathBuf::from(path))
+    }
+
+    /// Maps session ids to their display title, preferring summaries like the sidebar does.
+    pub fn session_titles(&self) -> HashMap<String, String> {
+        let files = self.files.lock().unwrap();
+        let summaries: HashMap<&str, &str> = files
+            .values()
+            .flat_map(|entry| entry.summaries.iter())
+            .map(|(leaf_uuid, summary)| (leaf_uuid.as_str(), summary.as_str()))
+            .collect();
+
+        files
+            .values()
+            .filter_map(|entry| {
+                let session = entry.session.as_ref()?;
+                let title = summaries
+                    .get(entry.last_message_uuid.as_str())
+                    .map(|summary| summary.to_string())
+                    .unwrap_or_else(|| session.title.clone());
+                Some((session.id.clone(), title))
+            })
+            .collect()
+    }
+
+    /// Drops entries for files that no longer exist on disk.
+    pub fn prune_missing(&self) {
+        let mut files = self.files.lock().unwrap();
+        let before = files.len();
+        files.retain(|path, _| Path::new(path).exists());
+        if files.len() != before {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let bytes = {
+            let files = self.files.lock().unwrap();
+            serde_json::to_vec(&IndexFile {
+                version: INDEX_VERSION,
+                files: files.clone(),
+            })?
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        // Write to a temporary file first so a crash never leaves a truncated cache
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, bytes).await?;
+        fs::rename(&tmp_path, &self.path).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use crate::types::{SessionFileEvent, SessionFileEventKind};
+use anyhow::Result;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Watches projects directories and reports session files that are created,
+/// appended to, truncated or deleted. Watching stops when this is dropped.
+pub struct SessionWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl SessionWatcher {
+    pub fn start(projects_dirs: &[PathBuf], on_event: impl Fn(SessionFileEvent) + Send + 'static) -> Result<Self> {
+        // Known sizes tell growth apart from creation, and from writes that
+        // don't change the content length
+        let mut sizes = HashMap::new();
+        for projects_dir in projects_dirs {
+            sizes.extend(Self::current_sizes(projects_dir));
+        }
+
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            let Ok(event) = result else {
+                return;
+            };
+            for path in event.paths {
+                if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                    continue;
+                }
+                if let Some(session_event) = Self::classify(&mut sizes, path) {
+                    on_event(session_event);
+                }
+            }
+        })?;
+        for projects_dir in projects_dirs {
+            watcher.watch(projects_dir, RecursiveMode::Recursive)?;
+        }
+
+        Ok(Self { _watcher: watcher })
+    }
+
+    fn current_sizes(projects_dir: &Path) -> HashMap<PathBuf, u64> {
+        let mut sizes = HashMap::new();
+        let Ok(projects) = std::fs::read_dir(projects_dir) else {
+            return sizes;
+        };
+
+        for project in projects.flatten() {
+            let Ok(files) = std::fs::read_dir(project.path()) else {
+                continue;
+            };
+            for file in files.flatten() {
+                let path = file.path();
+                if path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
+                    if let Ok(metadata) = file.metadata() {
+                        sizes.insert(path, metadata.len());
+                    }
+                }
+            }
+        }
+
+        sizes
+    }
+
+    // Compares the file against its last known size rather than trusting the
+    // event kind, which differs between platforms
+    fn classify(sizes: &mut HashMap<PathBuf, u64>, path: PathBuf) -> Option<SessionFileEvent> {
+        let size = std::fs::metadata(&path).ok().filter(|metadata| metadata.is_file()).map(|metadata| metadata.len());
+        let previous_size = sizes.get(&path).copied();
+
+        let kind = match (previous_size, size) {
+            (None, None) => return None,
+            (Some(_), None) => SessionFileEventKind::Removed,
+            (None, Some(_)) => SessionFileEventKind::Created,
+            (Some(previous), Some(size)) if size > previous => SessionFileEventKind::Grew,
+            (Some(previous), Some(size)) if size < previous => SessionFileEventKind::Truncated,
+            (Some(_), Some(_)) => return None,
+        };
+
+        match size {
+            Some(size) => sizes.insert(path.clone(), size),
+            None => sizes.remove(&path),
+        };
+
+        Some(SessionFileEvent {
+            kind,
+            session_id: path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+            project_path: path.parent().map(|parent| parent.to_string_lossy().to_string()).unwrap_or_default(),
+            file_path: path.to_string_lossy().to_string(),
+            size: size.unwrap_or(0),
+            previous_size: previous_size.unwrap_or(0),
+        })
+    }
+}
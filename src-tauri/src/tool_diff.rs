@@ -0,0 +1,146 @@
+use crate::types::{DiffHunk, DiffLine, DiffLineKind, DiffSource, FileDiff};
+use similar::{ChangeTag, TextDiff};
+use std::ops::Range;
+use std::time::Duration;
+
+// Lines of unchanged context kept around each change in diffs computed from input
+const CONTEXT_LINES: usize = 3;
+// Edits with huge strings fall back to a coarser diff rather than stalling a parse
+const DIFF_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// The change an `Edit`, `MultiEdit` or `Write` call makes, as unified diff hunks.
+/// The `structuredPatch` Claude Code records in the tool's result is preferred,
+/// since it is numbered by file line; otherwise the diff is computed from the input.
+pub fn file_diff(tool_name: &str, input: &serde_json::Value, tool_use_result: Option<&serde_json::Value>) -> Option<FileDiff> {
+    if !matches!(tool_name, "Edit" | "MultiEdit" | "Write") {
+        return None;
+    }
+    let file_path = input
+        .get("file_path")
+        .or_else(|| tool_use_result.and_then(|result| result.get("filePath")))
+        .and_then(|v| v.as_str())?
+        .to_string();
+
+    let patch = tool_use_result
+        .and_then(|result| result.get("structuredPatch"))
+        .and_then(|v| v.as_array())
+        .filter(|hunks| !hunks.is_empty());
+    if let Some(patch) = patch {
+        return Some(FileDiff {
+            file_path,
+            source: DiffSource::Patch,
+            hunks: patch.iter().filter_map(patch_hunk).collect(),
+        });
+    }
+
+    let str_field = |value: &serde_json::Value, field: &str| value.get(field).and_then(|v| v.as_str()).map(str::to_string);
+    let hunks = match tool_name {
+        "Edit" => diff_strings(&str_field(input, "old_string")?, &str_field(input, "new_string")?),
+        "MultiEdit" => input
+            .get("edits")
+            .and_then(|v| v.as_array())?
+            .iter()
+            .filter_map(|edit| Some(diff_strings(&str_field(edit, "old_string")?, &str_field(edit, "new_string")?)))
+            .flatten()
+            .collect(),
+        // Without a patch the previous content is unknown, so all of it shows as added
+        _ => diff_strings("", &str_field(input, "content")?),
+    };
+
+    Some(FileDiff {
+        file_path,
+        source: DiffSource::Input,
+        hunks,
+    })
+}
+
+// One hunk of a structuredPatch: {oldStart, oldLines, newStart, newLines, lines}
+// with each line prefixed by ' ', '-' or '+'
+fn patch_hunk(hunk: &serde_json::Value) -> Option<DiffHunk> {
+    let number = |field: &str| hunk.get(field).and_then(|v| v.as_u64()).map(|n| n as u32);
+    let old_start = number("oldStart")?;
+    let new_start = number("newStart")?;
+
+    let mut old_line = old_start;
+    let mut new_line = new_start;
+    let mut lines = Vec::new();
+    for line in hunk.get("lines").and_then(|v| v.as_array())?.iter().filter_map(|v| v.as_str()) {
+        let (kind, text) = match line.chars().next() {
+            Some('+') => (DiffLineKind::Added, &line[1..]),
+            Some('-') => (DiffLineKind::Removed, &line[1..]),
+            Some(' ') => (DiffLineKind::Context, &line[1..]),
+            // "\ No newline at end of file"
+            Some('\\') => continue,
+            _ => (DiffLineKind::Context, line),
+        };
+        lines.push(numbered_line(kind, text, &mut old_line, &mut new_line));
+    }
+
+    Some(DiffHunk {
+        old_start,
+        old_lines: number("oldLines").unwrap_or(0),
+        new_start,
+        new_lines: number("newLines").unwrap_or(0),
+        lines,
+    })
+}
+
+// Hunks between two snippets, numbered from the first line of the snippet
+fn diff_strings(old: &str, new: &str) -> Vec<DiffHunk> {
+    let diff = TextDiff::configure().timeout(DIFF_TIMEOUT).diff_lines(old, new);
+
+    diff.grouped_ops(CONTEXT_LINES)
+        .iter()
+        .filter_map(|group| {
+            let (first, last) = (group.first()?, group.last()?);
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+
+            let mut old_line = old_range.start as u32 + 1;
+            let mut new_line = new_range.start as u32 + 1;
+            let lines = group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| {
+                    let kind = match change.tag() {
+                        ChangeTag::Equal => DiffLineKind::Context,
+                        ChangeTag::Delete => DiffLineKind::Removed,
+                        ChangeTag::Insert => DiffLineKind::Added,
+                    };
+                    let text = change.value().trim_end_matches('\n').trim_end_matches('\r');
+                    numbered_line(kind, text, &mut old_line, &mut new_line)
+                })
+                .collect();
+
+            // As in unified diffs, an empty side starts at the line before it
+            let start = |range: &Range<usize>| (if range.is_empty() { range.start } else { range.start + 1 }) as u32;
+            Some(DiffHunk {
+                old_start: start(&old_range),
+                old_lines: old_range.len() as u32,
+                new_start: start(&new_range),
+                new_lines: new_range.len() as u32,
+                lines,
+            })
+        })
+        .collect()
+}
+
+fn numbered_line(kind: DiffLineKind, text: &str, old_line: &mut u32, new_line: &mut u32) -> DiffLine {
+    let take = |counter: &mut u32| {
+        let line = *counter;
+        *counter += 1;
+        Some(line)
+    };
+    let (old, new) = match kind {
+        DiffLineKind::Context => (take(old_line), take(new_line)),
+        DiffLineKind::Removed => (take(old_line), None),
+        DiffLineKind::Added => (None, take(new_line)),
+    };
+
+    DiffLine {
+        kind,
+        old_line: old,
+        new_line: new,
+        text: text.to_string(),
+    }
+}
@@ -0,0 +1,129 @@
+use crate::types::{ChatMessage, ContentBlock, InputCount, MessageContent, ToolStats};
+use std::collections::HashMap;
+
+// Number of most common inputs reported per tool
+const TOP_INPUTS: usize = 10;
+
+#[derive(Default)]
+struct ToolAccumulator {
+    calls: u64,
+    errors: u64,
+    output_sizes: Vec<u64>,
+    inputs: HashMap<String, u64>,
+}
+
+/// Collects per-tool statistics from the tool calls of parsed sessions.
+#[derive(Default)]
+pub struct ToolStatsCollector {
+    tools: HashMap<String, ToolAccumulator>,
+}
+
+impl ToolStatsCollector {
+    pub fn add_session(&mut self, messages: &[ChatMessage]) {
+        // Results that weren't merged into their call are matched up by tool_use_id
+        let mut results: HashMap<&str, &ContentBlock> = HashMap::new();
+        let mut calls: Vec<&ContentBlock> = Vec::new();
+        Self::collect_blocks(messages, &mut calls, &mut results);
+
+        for call in calls {
+            let ContentBlock::ToolUse { id, name, input, content, is_error, .. } = call else {
+                continue;
+            };
+            let (content, is_error) = match results.get(id.as_str()) {
+                Some(ContentBlock::ToolResult { content, is_error, .. }) => (content, is_error),
+                _ => (content, is_error),
+            };
+
+            let tool = self.tools.entry(name.clone()).or_default();
+            tool.calls += 1;
+            if *is_error == Some(true) {
+                tool.errors += 1;
+            }
+            if let Some(content) = content {
+                tool.output_sizes.push(content.len() as u64);
+            }
+            if let Some(input) = Self::input_key(name, input) {
+                *tool.inputs.entry(input).or_default() += 1;
+            }
+        }
+    }
+
+    fn collect_blocks<'a>(
+        messages: &'a [ChatMessage],
+        calls: &mut Vec<&'a ContentBlock>,
+        results: &mut HashMap<&'a str, &'a ContentBlock>,
+    ) {
+        for message in messages {
+            let MessageContent::Mixed(blocks) = &message.content else {
+                continue;
+            };
+            for block in blocks {
+                match block {
+                    ContentBlock::ToolUse { sidechain, .. } => {
+                        calls.push(block);
+                        // Tool calls made by sub-agents count too
+                        if let Some(sidechain) = sidechain {
+                            Self::collect_blocks(sidechain, calls, results);
+                        }
+                    }
+                    ContentBlock::ToolResult { tool_use_id, .. } => {
+                        results.insert(tool_use_id, block);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // The part of a tool's input worth counting, e.g. the command run by Bash
+    fn input_key(tool_name: &str, input: &serde_json::Value) -> Option<String> {
+        let field = match tool_name {
+            "Bash" => "command",
+            "Read" | "Edit" | "MultiEdit" | "Write" => "file_path",
+            "NotebookRead" | "NotebookEdit" => "notebook_path",
+            "Grep" | "Glob" => "pattern",
+            "LS" => "path",
+            "WebFetch" => "url",
+            "WebSearch" => "query",
+            "Task" => "description",
+            _ => return None,
+        };
+        input.get(field).and_then(|v| v.as_str()).map(|s| s.trim().to_string())
+    }
+
+    /// The statistics of every tool, most used first.
+    pub fn finish(self) -> Vec<ToolStats> {
+        let mut stats: Vec<ToolStats> = self
+            .tools
+            .into_iter()
+            .map(|(tool_name, mut tool)| {
+                tool.output_sizes.sort_unstable();
+                let median_output_bytes = match tool.output_sizes.len() {
+                    0 => None,
+                    len if len % 2 == 1 => Some(tool.output_sizes[len / 2]),
+                    len => Some((tool.output_sizes[len / 2 - 1] + tool.output_sizes[len / 2]) / 2),
+                };
+
+                let mut top_inputs: Vec<InputCount> = tool
+                    .inputs
+                    .into_iter()
+                    .map(|(input, count)| InputCount { input, count })
+                    .collect();
+                top_inputs.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.input.cmp(&b.input)));
+                top_inputs.truncate(TOP_INPUTS);
+
+                ToolStats {
+                    tool_name,
+                    calls: tool.calls,
+                    errors: tool.errors,
+                    error_rate: tool.errors as f64 / tool.calls as f64,
+                    median_output_bytes,
+                    top_inputs,
+                }
+            })
+            .collect();
+
+        stats.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.tool_name.cmp(&b.tool_name)));
+        stats
+    }
+}
@@ -8,6 +8,10 @@ pub struct ChatSession {
     pub project_path: String,
     pub message_count: usize,
     pub last_updated: String,
+    #[serde(default)]
+    pub usage: TokenUsage,
+    #[serde(default)]
+    pub cost_usd: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,12 +19,218 @@ pub struct ChatMessage {
     pub uuid: String,
     pub parent_uuid: Option<String>,
     pub timestamp: String,
-    pub message_type: String, // "user" or "assistant"
+    pub message_type: String, // "user", "assistant" or "system"
     pub content: MessageContent,
     pub tool_use_id: Option<String>,
     pub cwd: Option<String>,
     pub version: Option<String>,
     pub model: Option<String>, // Model name for assistant messages
+    #[serde(default)]
+    pub is_sidechain: bool, // Written by a sub-agent rather than the main conversation
+    pub usage: Option<TokenUsage>, // Assistant messages only
+    pub cost_usd: Option<f64>,
+    #[serde(default)]
+    pub kind: MessageKind,
+    #[serde(default)]
+    pub images: Vec<ImageRef>, // Images attached to the message, e.g. pasted screenshots
+}
+
+// What a message in the timeline is, beyond who wrote it. Tagged by "type" so
+// the frontend can switch on it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageKind {
+    #[default]
+    Conversation, // A prompt typed by the user or a reply from the model
+    Meta, // Context Claude Code added on the user's behalf (isMeta)
+    Command { name: String, args: String }, // A slash command such as /compact
+    CommandOutput { output: String }, // What a local slash command printed
+    CompactSummary, // Summary of the history that /compact replaced
+    CompactBoundary { trigger: Option<String>, pre_tokens: Option<u64> }, // Where history was compacted
+    Hook { event: Option<String>, tool_use_id: Option<String>, level: Option<String> }, // A hook run
+    ApiError { retry_attempt: Option<u32>, max_retries: Option<u32>, retry_in_ms: Option<f64> }, // A failed API request
+    System { subtype: Option<String>, level: Option<String> }, // Any other notice from Claude Code
+}
+
+// Token counts reported by the API for one assistant message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    Html, // Standalone page with inline styles
+}
+
+// A line of a session file that was skipped because it couldn't be parsed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseIssue {
+    pub line: usize, // 1-based
+    pub error: String,
+    pub excerpt: String, // Start of the raw line
+}
+
+// Session file with skipped lines; only the first lines are listed in `issues`
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseDiagnostics {
+    pub session_id: Option<String>, // None when no message in the file could be read
+    pub project_path: String,
+    pub file_path: String,
+    pub issue_count: usize,
+    pub issues: Vec<ParseIssue>,
+}
+
+// A directory sessions are read from, either a Claude config directory or its
+// projects directory; unavailable when it doesn't exist, e.g. an unmounted drive
+#[derive(Debug, Clone, Serialize)]
+pub struct DataRoot {
+    pub path: String,
+    pub projects_path: String,
+    pub available: bool,
+}
+
+// Sessions to put in an export archive: one project or all of them, optionally
+// only those active within a date range
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ArchiveRequest {
+    pub project_path: Option<String>,
+    #[serde(default)]
+    pub range: UsageRange,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveSummary {
+    pub path: String,
+    pub sessions: usize,
+    pub bytes: u64,
+}
+
+// Payload of the "session-file-changed" event emitted by the session watcher
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionFileEvent {
+    pub kind: SessionFileEventKind,
+    pub session_id: String, // Session files are named after their session id
+    pub project_path: String,
+    pub file_path: String,
+    pub size: u64,
+    pub previous_size: u64, // Offset the appended bytes start at when the file grew
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionFileEventKind {
+    Created,
+    Grew,
+    Truncated, // Rewritten with less content, previous offsets are no longer valid
+    Removed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionTail {
+    // Streamed replies can continue a message from the previous read, in which case
+    // the first message shares its message id (the part of uuid after '#')
+    pub messages: Vec<ChatMessage>,
+    pub next_offset: u64,
+    pub reset: bool, // The file was rewritten and read again from the start
+}
+
+// Inclusive YYYY-MM-DD bounds in local time, open-ended when None
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageRange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageGroupBy {
+    Day,
+    Week, // Keyed by the date of the week's Monday
+    Project,
+    Model,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageStats {
+    pub key: String, // Day, week start, project path or model depending on the grouping
+    pub sessions: usize,
+    pub user_messages: u64, // Prompts typed by the user, tool results excluded
+    pub assistant_messages: u64,
+    pub tool_calls: u64,
+    pub usage: TokenUsage,
+    pub cost_usd: f64,
+    pub active_ms: u64, // Time between messages, ignoring pauses over five minutes
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolStats {
+    pub tool_name: String,
+    pub calls: u64,
+    pub errors: u64, // Calls whose result had is_error set
+    pub error_rate: f64,
+    pub median_output_bytes: Option<u64>, // None when no result had text content
+    pub top_inputs: Vec<InputCount>, // E.g. Bash commands or edited files, most common first
+}
+
+// A file a session read or changed through its tool calls
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionFile {
+    pub path: String,
+    pub reads: u64,
+    pub edits: u64, // Edit, MultiEdit and NotebookEdit calls
+    pub writes: u64,
+    pub globs: u64, // Glob searches in this directory
+    pub created: bool, // Written by the session when it didn't exist yet
+    pub first_touched: String,
+    pub last_touched: String,
+    pub final_edit: Option<FileEdit>, // The last call that changed the file
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileEdit {
+    pub tool_name: String,
+    pub message_uuid: String,
+    pub timestamp: String,
+    pub diff: Option<FileDiff>, // None for notebook edits
+}
+
+// A session that touched the file passed to get_file_history
+#[derive(Debug, Clone, Serialize)]
+pub struct FileHistoryEntry {
+    pub session: ChatSession,
+    pub file: SessionFile,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InputCount {
+    pub input: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyCost {
+    pub date: String, // YYYY-MM-DD in local time
+    pub usage: TokenUsage,
+    pub cost_usd: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,17 +240,157 @@ pub enum MessageContent {
     Mixed(Vec<ContentBlock>),
 }
 
+// One block of a message, tagged by "type" as in the API. Tool calls also carry
+// their result once it has been merged in from the user message that answered them
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ContentBlock {
-    #[serde(rename = "type")]
-    pub block_type: String, // "text", "tool_use", "tool_result", "thinking"
-    pub text: Option<String>,
-    pub name: Option<String>, // Tool name
-    pub input: Option<serde_json::Value>,
-    pub tool_use_id: Option<String>,
-    pub content: Option<String>, // Tool result content
-    pub tool_use_result: Option<serde_json::Value>, // For TodoWrite and other structured results
-    pub thinking: Option<String>, // For thinking blocks
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text {
+        text: String,
+    },
+    Thinking {
+        thinking: String,
+        signature: Option<String>,
+    },
+    RedactedThinking {
+        data: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+        content: Option<String>, // Result text, once merged
+        is_error: Option<bool>,
+        tool_use_result: Option<serde_json::Value>, // Structured result, e.g. TodoWrite's new todos
+        #[serde(default)]
+        images: Vec<ImageRef>, // Images in the result, once merged
+        diff: Option<FileDiff>, // The change made by an Edit, MultiEdit or Write call
+        sidechain: Option<Vec<ChatMessage>>, // Sub-agent transcript of a Task tool call
+    },
+    ToolResult {
+        tool_use_id: String,
+        #[serde(default, deserialize_with = "deserialize_tool_result_text")]
+        content: Option<String>,
+        is_error: Option<bool>,
+        tool_use_result: Option<serde_json::Value>,
+        // Image blocks of an array-form result, filled in from the cache as they're parsed
+        #[serde(default)]
+        images: Vec<ImageRef>,
+    },
+    Image {
+        source: serde_json::Value, // Without its base64 data once the image is cached
+        #[serde(default)]
+        image: Option<ImageRef>,
+    },
+    Document {
+        source: serde_json::Value,
+        title: Option<String>,
+    },
+    ServerToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    #[serde(rename = "web_search_tool_result")]
+    WebSearchResult {
+        tool_use_id: String,
+        content: serde_json::Value,
+    },
+    // Any other block is kept as written, so newer block types reach the frontend intact
+    #[serde(untagged)]
+    Unknown(serde_json::Value),
+}
+
+// An image decoded into the image cache. The webview loads it from the `chatimage`
+// protocol by id rather than receiving the image data over IPC, and can size a
+// thumbnail from the dimensions before it has loaded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageRef {
+    pub id: String, // Content hash and extension, e.g. "3f9a….png"
+    pub media_type: String,
+    pub width: Option<u32>, // None when the header couldn't be read
+    pub height: Option<u32>,
+    pub byte_size: u64,
+}
+
+// The change a file-editing tool call makes, as unified diff hunks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileDiff {
+    pub file_path: String,
+    pub source: DiffSource,
+    pub hunks: Vec<DiffHunk>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffSource {
+    Patch, // The structuredPatch of the tool's result, numbered by file line
+    Input, // Computed from the call's input, numbered from the start of the edited text
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffHunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub old_line: Option<u32>, // None for added lines
+    pub new_line: Option<u32>, // None for removed lines
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffLineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+// Tool results hold either a string or a list of blocks; the text blocks are joined
+fn deserialize_tool_result_text<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let content = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(match content {
+        Some(serde_json::Value::String(text)) => Some(text),
+        Some(serde_json::Value::Array(blocks)) => {
+            let texts: Vec<&str> = blocks
+                .iter()
+                .filter_map(|block| block.get("text").and_then(|v| v.as_str()))
+                .collect();
+            (!texts.is_empty()).then(|| texts.join("\n"))
+        }
+        _ => None,
+    })
+}
+
+// Messages of a session linked by parent_uuid. Nodes are listed in file order and
+// refer to each other by uuid, so deep conversations don't become deeply nested JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationTree {
+    pub session_id: String,
+    pub nodes: Vec<ConversationNode>,
+    pub roots: Vec<String>,
+    pub leaves: Vec<String>,
+    pub active_leaf: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationNode {
+    pub uuid: String, // Line uuid, without the message id suffix of ChatMessage::uuid
+    pub parent_uuid: Option<String>, // None for roots, including parents outside this session
+    pub children: Vec<String>,
+    pub message: ChatMessage,
+    pub is_active: bool, // On the path from a root to the active leaf
+    pub summary: Option<String>, // Set when a summary was written for this node
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +398,8 @@ pub struct ProjectFolder {
     pub name: String,
     pub path: String,
     pub chat_sessions: Vec<ChatSession>,
+    pub usage: TokenUsage,
+    pub cost_usd: f64,
 }
 
 // Raw JSONL message structure for parsing
@@ -55,32 +407,340 @@ pub struct ProjectFolder {
 pub struct RawJsonlMessage {
     #[serde(rename = "parentUuid")]
     pub parent_uuid: Option<String>,
+    #[serde(default)]
     pub cwd: String,
     #[serde(rename = "sessionId")]
     pub session_id: String,
+    #[serde(default)]
     pub version: String,
     #[serde(rename = "type")]
     pub message_type: String,
-    pub message: RawMessage,
+    #[serde(default)]
+    pub message: RawMessage, // Empty for system records, which carry `content` instead
+    pub content: Option<serde_json::Value>,
     pub uuid: String,
     pub timestamp: String,
+    #[serde(rename = "isSidechain", default)]
+    pub is_sidechain: bool,
     #[serde(rename = "toolUseResult")]
     pub tool_use_result: Option<serde_json::Value>,
+    #[serde(rename = "isMeta", default)]
+    pub is_meta: bool,
+    #[serde(rename = "isCompactSummary", default)]
+    pub is_compact_summary: bool,
+    #[serde(rename = "isApiErrorMessage", default)]
+    pub is_api_error_message: bool,
+    // The remaining fields are only written on system records
+    pub subtype: Option<String>,
+    pub level: Option<String>,
+    #[serde(rename = "toolUseID")]
+    pub tool_use_id: Option<String>,
+    #[serde(rename = "compactMetadata")]
+    pub compact_metadata: Option<RawCompactMetadata>,
+    #[serde(rename = "retryAttempt")]
+    pub retry_attempt: Option<u32>,
+    #[serde(rename = "maxRetries")]
+    pub max_retries: Option<u32>,
+    #[serde(rename = "retryInMs")]
+    pub retry_in_ms: Option<f64>,
 }
 
 #[derive(Debug, Deserialize)]
+pub struct RawCompactMetadata {
+    pub trigger: Option<String>,
+    #[serde(rename = "preTokens")]
+    pub pre_tokens: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
 pub struct RawMessage {
     pub content: serde_json::Value, // Can be string or array
     pub id: Option<String>,
     pub model: Option<String>,
+    pub usage: Option<TokenUsage>,
 }
 
-#[derive(Debug, Serialize)]
+// One search hit per message; when several fields of the message match, the
+// top-level snippet and match_type describe the best one and `matches` has all
+#[derive(Debug, Clone, Serialize)]
 pub struct SearchResult {
     pub session_id: String,
     pub message_uuid: String,
     pub snippet: String,
-    pub match_type: String, // "content", "tool_name", "tool_result"
+    pub match_type: String, // "content", "thinking", "tool_name", "tool_input", "tool_result", "tool_structured_result"
+    pub highlight: Option<SnippetHighlight>,
+    pub project_path: String,
+    pub session_title: Option<String>,
+    pub timestamp: String,
+    pub role: String, // "user" or "assistant"
+    pub model: Option<String>,
+    pub tool_name: Option<String>,
+    pub matches: Vec<SearchMatch>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchMatch {
+    pub match_type: String,
+    pub snippet: String,
+    pub highlight: Option<SnippetHighlight>,
+    pub tool_name: Option<String>,
+}
+
+// Position of the match inside a snippet, both in UTF-8 bytes and in characters
+#[derive(Debug, Clone, Serialize)]
+pub struct SnippetHighlight {
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub char_start: usize,
+    pub char_end: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    pub next_cursor: Option<String>, // Pass back to fetch the next page, None on the last one
+}
+
+// Payload of the "search-results" event emitted by search_chats_stream
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchBatch {
+    pub search_id: u64,
+    pub results: Vec<SearchResult>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    #[default]
+    Text,  // Query syntax with exact words and phrases
+    Regex, // The whole query is a regular expression
+    Fuzzy, // Query syntax, but words tolerate typos
+}
+
+// Tagged by "kind" so the frontend can tell a bad query apart from a failure
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SearchError {
+    InvalidRegex { pattern: String, message: String },
+    InvalidQuery { message: String },
+    Internal { message: String },
+}
+
+impl std::fmt::Display for SearchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchError::InvalidRegex { pattern, message } => write!(f, "Invalid regex \"{}\": {}", pattern, message),
+            SearchError::InvalidQuery { message } | SearchError::Internal { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+impl From<anyhow::Error> for SearchError {
+    fn from(error: anyhow::Error) -> Self {
+        SearchError::Internal { message: error.to_string() }
+    }
+}
+
+// What kind of failure a command hit, so the frontend can react to it, e.g. by
+// offering to pick a different data root
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandErrorKind {
+    SessionNotFound,
+    DataRootMissing,
+    FileNotFound,
+    PermissionDenied,
+    CorruptData,
+    InvalidInput,
+    Internal,
+}
+
+// Error returned by commands, with the file and line it concerns when known
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandError {
+    pub kind: CommandErrorKind,
+    pub message: String,
+    pub path: Option<String>,
+    pub line: Option<usize>, // 1-based line in `path`
+}
+
+impl CommandError {
+    pub fn new(kind: CommandErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            path: None,
+            line: None,
+        }
+    }
+
+    pub fn with_path(mut self, path: &std::path::Path) -> Self {
+        self.path = Some(path.to_string_lossy().to_string());
+        self
+    }
+
+    pub fn with_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// An I/O error while accessing `path`.
+    pub fn io(error: std::io::Error, path: &std::path::Path) -> Self {
+        let kind = Self::io_kind(&error);
+        let message = match kind {
+            CommandErrorKind::FileNotFound => format!("{} does not exist", path.display()),
+            CommandErrorKind::PermissionDenied => format!("Permission denied: {}", path.display()),
+            CommandErrorKind::CorruptData => format!("{} contains invalid data: {}", path.display(), error),
+            _ => format!("Failed to access {}: {}", path.display(), error),
+        };
+        Self::new(kind, message).with_path(path)
+    }
+
+    fn io_kind(error: &std::io::Error) -> CommandErrorKind {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => CommandErrorKind::FileNotFound,
+            std::io::ErrorKind::PermissionDenied => CommandErrorKind::PermissionDenied,
+            std::io::ErrorKind::InvalidData => CommandErrorKind::CorruptData,
+            _ => CommandErrorKind::Internal,
+        }
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<anyhow::Error> for CommandError {
+    fn from(error: anyhow::Error) -> Self {
+        // The message keeps any context added on the way up, the rest comes from
+        // the most specific error in the chain
+        let message = format!("{:#}", error);
+        if let Some(cause) = error.chain().find_map(|cause| cause.downcast_ref::<CommandError>()) {
+            return Self { message, ..cause.clone() };
+        }
+        match error.chain().find_map(|cause| cause.downcast_ref::<std::io::Error>()) {
+            Some(io_error) => Self::new(Self::io_kind(io_error), message),
+            None => Self::new(CommandErrorKind::Internal, message),
+        }
+    }
+}
+
+impl ParseIssue {
+    const EXCERPT_CHARS: usize = 200;
+
+    pub fn new(line: usize, error: String, raw_line: &str) -> Self {
+        let mut excerpt: String = raw_line.chars().take(Self::EXCERPT_CHARS).collect();
+        if excerpt.len() < raw_line.len() {
+            excerpt.push('…');
+        }
+        Self { line, error, excerpt }
+    }
+}
+
+impl SnippetHighlight {
+    pub fn new(snippet: &str, byte_range: std::ops::Range<usize>) -> Self {
+        let char_start = snippet[..byte_range.start].chars().count();
+        Self {
+            byte_start: byte_range.start,
+            byte_end: byte_range.end,
+            char_start,
+            char_end: char_start + snippet[byte_range].chars().count(),
+        }
+    }
+}
+
+// Events a hook can run on, as they appear in hook notices
+const HOOK_EVENTS: &[&str] = &[
+    "PreToolUse",
+    "PostToolUse",
+    "Notification",
+    "UserPromptSubmit",
+    "SessionStart",
+    "SessionEnd",
+    "SubagentStop",
+    "Stop",
+    "PreCompact",
+];
+
+impl MessageKind {
+    pub fn from_raw(raw: &RawJsonlMessage, text: &str) -> Self {
+        match raw.message_type.as_str() {
+            "system" => Self::from_system_record(raw, text),
+            "assistant" if raw.is_api_error_message => MessageKind::ApiError {
+                retry_attempt: None,
+                max_retries: None,
+                retry_in_ms: None,
+            },
+            "user" if raw.is_compact_summary => MessageKind::CompactSummary,
+            "user" => {
+                if let Some(name) = Self::tag_content(text, "command-name") {
+                    MessageKind::Command {
+                        name: name.to_string(),
+                        args: Self::tag_content(text, "command-args").unwrap_or_default().to_string(),
+                    }
+                } else if let Some(output) =
+                    Self::tag_content(text, "local-command-stdout").or_else(|| Self::tag_content(text, "local-command-stderr"))
+                {
+                    MessageKind::CommandOutput { output: output.to_string() }
+                } else if raw.is_meta {
+                    MessageKind::Meta
+                } else {
+                    MessageKind::Conversation
+                }
+            }
+            _ => MessageKind::Conversation,
+        }
+    }
+
+    fn from_system_record(raw: &RawJsonlMessage, text: &str) -> Self {
+        let subtype = raw.subtype.as_deref().unwrap_or_default();
+        if subtype == "compact_boundary" {
+            let metadata = raw.compact_metadata.as_ref();
+            return MessageKind::CompactBoundary {
+                trigger: metadata.and_then(|metadata| metadata.trigger.clone()),
+                pre_tokens: metadata.and_then(|metadata| metadata.pre_tokens),
+            };
+        }
+        if subtype == "api_error" {
+            return MessageKind::ApiError {
+                retry_attempt: raw.retry_attempt,
+                max_retries: raw.max_retries,
+                retry_in_ms: raw.retry_in_ms,
+            };
+        }
+
+        // Hook notices name the event they ran on, e.g. "PostToolUse:Edit [prettier] completed"
+        let event = HOOK_EVENTS
+            .iter()
+            .find(|event| text.starts_with(*event) || text.contains(&format!("{} hook", event)))
+            .map(|event| event.to_string())
+            .or_else(|| (subtype == "stop_hook_summary").then(|| "Stop".to_string()));
+        if event.is_some() || subtype.contains("hook") {
+            return MessageKind::Hook {
+                event,
+                tool_use_id: raw.tool_use_id.clone(),
+                level: raw.level.clone(),
+            };
+        }
+
+        MessageKind::System {
+            subtype: raw.subtype.clone(),
+            level: raw.level.clone(),
+        }
+    }
+
+    // Text between <tag> and </tag>, as Claude Code wraps command names and output
+    fn tag_content<'a>(text: &'a str, tag: &str) -> Option<&'a str> {
+        let start = text.find(&format!("<{}>", tag))? + tag.len() + 2;
+        let end = start + text[start..].find(&format!("</{}>", tag))?;
+        Some(text[start..end].trim())
+    }
 }
 
 impl ChatMessage {
@@ -90,7 +750,10 @@ impl ChatMessage {
             MessageContent::Mixed(blocks) => {
                 blocks
                     .iter()
-                    .filter_map(|block| block.text.as_ref())
+                    .filter_map(|block| match block {
+                        ContentBlock::Text { text } => Some(text),
+                        _ => None,
+                    })
                     .cloned()
                     .collect::<Vec<_>>()
                     .join("\n")
@@ -104,11 +767,25 @@ impl ChatMessage {
         match &self.content {
             MessageContent::Text(_) => false,
             MessageContent::Mixed(blocks) => {
-                blocks.iter().any(|block| block.block_type == "tool_use")
+                blocks.iter().any(|block| matches!(block, ContentBlock::ToolUse { .. }))
             }
         }
     }
 
+    /// The cached images among the message's own blocks, not counting tool results.
+    pub fn attached_images(&self) -> Vec<ImageRef> {
+        match &self.content {
+            MessageContent::Text(_) => Vec::new(),
+            MessageContent::Mixed(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Image { image, .. } => image.clone(),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
 }
 
 impl ChatSession {
@@ -127,6 +804,8 @@ impl ChatSession {
             project_path,
             message_count: 0,
             last_updated: first_message.timestamp.clone(),
+            usage: TokenUsage::default(),
+            cost_usd: 0.0,
         }
     }
 
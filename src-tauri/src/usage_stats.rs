@@ -0,0 +1,223 @@
+use crate::types::{CommandError, CommandErrorKind, RawJsonlMessage, TokenUsage, UsageGroupBy, UsageRange, UsageStats};
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+// Pauses between two messages longer than this don't count as active time
+const IDLE_GAP_MS: i64 = 5 * 60 * 1000;
+
+/// Activity of one session on one local day with one model. Sessions are cached
+/// as a list of these so every rollup can be computed without re-reading files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityRecord {
+    pub date: String, // YYYY-MM-DD in local time
+    pub model: String, // Empty until the session's first assistant reply
+    pub user_messages: u64,
+    pub assistant_messages: u64,
+    pub tool_calls: u64,
+    pub usage: TokenUsage,
+    pub active_ms: u64,
+}
+
+/// Day of an RFC 3339 timestamp in the user's time zone.
+pub fn local_date(timestamp: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|date| date.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|_| timestamp.chars().take(10).collect())
+}
+
+/// Builds the activity records of a session from its lines, in file order.
+#[derive(Default)]
+pub struct ActivityTracker {
+    records: Vec<ActivityRecord>,
+    seen_messages: HashSet<String>,
+    // Streamed responses repeat the usage on every line of a message, keep the last
+    message_usage: HashMap<String, (usize, TokenUsage)>,
+    // User messages are attributed to the model that answered before them
+    current_model: String,
+    last_timestamp_ms: Option<i64>,
+}
+
+impl ActivityTracker {
+    pub fn add(&mut self, raw: &RawJsonlMessage) {
+        let model = match (&raw.message.model, raw.message_type.as_str()) {
+            // Placeholders like "<synthetic>" aren't a model the session switched to
+            (Some(model), "assistant") if !model.starts_with('<') => {
+                self.current_model = model.clone();
+                model.clone()
+            }
+            (Some(model), "assistant") => model.clone(),
+            _ => self.current_model.clone(),
+        };
+        let record = self.record_for(local_date(&raw.timestamp), model);
+
+        if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(&raw.timestamp) {
+            let timestamp_ms = timestamp.timestamp_millis();
+            if let Some(gap) = self.last_timestamp_ms.map(|last| timestamp_ms - last) {
+                if (0..IDLE_GAP_MS).contains(&gap) {
+                    self.records[record].active_ms += gap as u64;
+                }
+            }
+            self.last_timestamp_ms = Some(timestamp_ms);
+        }
+
+        let blocks = raw.message.content.as_array();
+        let count_blocks = |block_type: &str| {
+            blocks.map_or(0, |blocks| {
+                blocks
+                    .iter()
+                    .filter(|block| block.get("type").and_then(|v| v.as_str()) == Some(block_type))
+                    .count()
+            })
+        };
+
+        match raw.message_type.as_str() {
+            "user" => {
+                // Tool results are sent back automatically, they aren't prompts
+                let only_tool_results = blocks.is_some_and(|blocks| !blocks.is_empty() && count_blocks("tool_result") == blocks.len());
+                if !only_tool_results {
+                    self.records[record].user_messages += 1;
+                }
+            }
+            "assistant" => {
+                let message_key = raw.message.id.clone().unwrap_or_else(|| raw.uuid.clone());
+                if self.seen_messages.insert(message_key.clone()) {
+                    self.records[record].assistant_messages += 1;
+                }
+                self.records[record].tool_calls += count_blocks("tool_use") as u64;
+                if let Some(usage) = raw.message.usage {
+                    self.message_usage.insert(message_key, (record, usage));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn record_for(&mut self, date: String, model: String) -> usize {
+        if let Some(position) = self
+            .records
+            .iter()
+            .position(|record| record.date == date && record.model == model)
+        {
+            return position;
+        }
+
+        self.records.push(ActivityRecord {
+            date,
+            model,
+            user_messages: 0,
+            assistant_messages: 0,
+            tool_calls: 0,
+            usage: TokenUsage::default(),
+            active_ms: 0,
+        });
+        self.records.len() - 1
+    }
+
+    pub fn finish(mut self) -> Vec<ActivityRecord> {
+        for (record, usage) in self.message_usage.into_values() {
+            self.records[record].usage += usage;
+        }
+        self.records
+    }
+}
+
+impl UsageRange {
+    pub fn validate(&self) -> Result<()> {
+        for date in self.from.iter().chain(self.to.iter()) {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+                let message = format!("Invalid date \"{}\", expected YYYY-MM-DD", date);
+                CommandError::new(CommandErrorKind::InvalidInput, message)
+            })?;
+        }
+        Ok(())
+    }
+
+    fn contains(&self, date: &str) -> bool {
+        // YYYY-MM-DD strings order the same way as the dates they represent
+        self.from.as_deref().is_none_or(|from| date >= from) && self.to.as_deref().is_none_or(|to| date <= to)
+    }
+
+    /// Whether any day from `first` to `last` falls within the range.
+    pub fn overlaps(&self, first: &str, last: &str) -> bool {
+        self.from.as_deref().is_none_or(|from| last >= from) && self.to.as_deref().is_none_or(|to| first <= to)
+    }
+}
+
+/// Sums activity records into one `UsageStats` per group.
+pub struct UsageRollup {
+    range: UsageRange,
+    group_by: UsageGroupBy,
+    groups: HashMap<String, (UsageStats, HashSet<String>)>,
+}
+
+// Group key of activity that can't be attributed to a model
+const UNKNOWN_MODEL: &str = "unknown";
+
+impl UsageRollup {
+    pub fn new(range: UsageRange, group_by: UsageGroupBy) -> Result<Self> {
+        range.validate()?;
+        Ok(Self {
+            range,
+            group_by,
+            groups: HashMap::new(),
+        })
+    }
+
+    pub fn add(&mut self, session_id: &str, project: &str, record: &ActivityRecord, cost_usd: f64) {
+        if !self.range.contains(&record.date) {
+            return;
+        }
+
+        let key = match self.group_by {
+            UsageGroupBy::Day => record.date.clone(),
+            UsageGroupBy::Week => Self::week_start(&record.date),
+            UsageGroupBy::Project => project.to_string(),
+            // Messages sent before the session's first reply have no model yet
+            UsageGroupBy::Model if record.model.is_empty() => UNKNOWN_MODEL.to_string(),
+            UsageGroupBy::Model => record.model.clone(),
+        };
+
+        let (stats, sessions) = self.groups.entry(key.clone()).or_insert_with(|| {
+            let stats = UsageStats {
+                key,
+                sessions: 0,
+                user_messages: 0,
+                assistant_messages: 0,
+                tool_calls: 0,
+                usage: TokenUsage::default(),
+                cost_usd: 0.0,
+                active_ms: 0,
+            };
+            (stats, HashSet::new())
+        });
+
+        sessions.insert(session_id.to_string());
+        stats.sessions = sessions.len();
+        stats.user_messages += record.user_messages;
+        stats.assistant_messages += record.assistant_messages;
+        stats.tool_calls += record.tool_calls;
+        stats.usage += record.usage;
+        stats.cost_usd += cost_usd;
+        stats.active_ms += record.active_ms;
+    }
+
+    // Weeks are keyed by the date of their Monday
+    fn week_start(date: &str) -> String {
+        match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            Ok(date) => {
+                let monday = date - chrono::Duration::days(date.weekday().num_days_from_monday() as i64);
+                monday.format("%Y-%m-%d").to_string()
+            }
+            Err(_) => date.to_string(),
+        }
+    }
+
+    /// The groups ordered by key, so days and weeks come out oldest first.
+    pub fn finish(self) -> Vec<UsageStats> {
+        let mut stats: Vec<UsageStats> = self.groups.into_values().map(|(stats, _)| stats).collect();
+        stats.sort_by(|a, b| a.key.cmp(&b.key));
+        stats
+    }
+}
@@ -1,5 +1,5 @@
 import React, { useEffect, useRef } from "react";
-import { MessageSquare, User, Bot, Clock, MoreHorizontal, Copy, ExternalLink } from "lucide-react";
+import { MessageSquare, User, Bot, Clock, MoreHorizontal, Copy, ExternalLink, Info, AlertTriangle, Scissors, Webhook } from "lucide-react";
 import { useChatMessages } from "@/hooks/useChats";
 import { MessageRenderer } from "@/components/MessageRenderer";
 import { Skeleton } from "@/components/ui/skeleton";
@@ -161,7 +161,50 @@ interface MessageBlockProps {
   message: ChatMessage;
 }
 
+const isSystemNotice = (message: ChatMessage) =>
+  message.message_type === "system" || message.kind?.type === "api_error";
+
+// Label and icon of a hook, compaction or other notice Claude Code wrote into the session
+const describeNotice = (message: ChatMessage) => {
+  const kind = message.kind;
+  switch (kind?.type) {
+    case "hook":
+      return { label: kind.event ? `Hook · ${kind.event}` : "Hook", icon: Webhook, warning: kind.level === "error" || kind.level === "warning" };
+    case "compact_boundary":
+      return { label: kind.trigger === "auto" ? "Conversation compacted automatically" : "Conversation compacted", icon: Scissors, warning: false };
+    case "api_error":
+      return { label: "API error", icon: AlertTriangle, warning: true };
+    case "system":
+      return { label: kind.subtype ? `System · ${kind.subtype}` : "System", icon: Info, warning: kind.level === "error" || kind.level === "warning" };
+    default:
+      return { label: "System", icon: Info, warning: false };
+  }
+};
+
+const SystemNotice: React.FC<MessageBlockProps> = ({ message }) => {
+  const { label, icon: Icon, warning } = describeNotice(message);
+
+  return (
+    <div className={cn(
+      "mb-6 rounded-md border border-dashed px-3 py-2 text-sm text-muted-foreground",
+      warning && "border-amber-300 text-amber-800 dark:border-amber-700 dark:text-amber-300"
+    )}>
+      <div className="flex items-center gap-2 text-xs font-medium">
+        <Icon className="h-3.5 w-3.5" />
+        <span>{label}</span>
+      </div>
+      <div className="mt-1 text-xs">
+        <MessageRenderer message={message} />
+      </div>
+    </div>
+  );
+};
+
 const MessageBlock: React.FC<MessageBlockProps> = ({ message }) => {
+  if (isSystemNotice(message)) {
+    return <SystemNotice message={message} />;
+  }
+
   const isUser = message.message_type === "user";
   const isAssistant = message.message_type === "assistant";
 
@@ -0,0 +1,106 @@
+import React from "react";
+import { cn } from "@/lib/utils";
+import type { DiffHunk, DiffLine, FileDiff } from "@/types/chat";
+
+interface DiffViewProps {
+  diff: FileDiff;
+  className?: string;
+}
+
+interface DiffRow {
+  left: DiffLine | null;
+  right: DiffLine | null;
+}
+
+// Pairs each run of removed lines with the added lines that follow it, so
+// changed lines sit next to each other
+const toRows = (hunk: DiffHunk): DiffRow[] => {
+  const rows: DiffRow[] = [];
+  let removed: DiffLine[] = [];
+  let added: DiffLine[] = [];
+
+  const flush = () => {
+    for (let i = 0; i < Math.max(removed.length, added.length); i++) {
+      rows.push({ left: removed[i] ?? null, right: added[i] ?? null });
+    }
+    removed = [];
+    added = [];
+  };
+
+  for (const line of hunk.lines) {
+    if (line.kind === "removed") {
+      if (added.length) flush();
+      removed.push(line);
+    } else if (line.kind === "added") {
+      added.push(line);
+    } else {
+      flush();
+      rows.push({ left: line, right: line });
+    }
+  }
+  flush();
+  return rows;
+};
+
+const Cell: React.FC<{ line: DiffLine | null; side: "old" | "new" }> = ({
+  line,
+  side,
+}) => {
+  const number = line && (side === "old" ? line.old_line : line.new_line);
+  const changed = line && line.kind !== "context";
+
+  return (
+    <>
+      <td className="select-none px-2 text-right text-gray-400 align-top w-10">
+        {number ?? ""}
+      </td>
+      <td
+        className={cn(
+          "px-2 whitespace-pre-wrap break-all align-top w-1/2",
+          !line && "bg-gray-100 dark:bg-gray-800/50",
+          changed &&
+            side === "old" &&
+            "bg-red-50 text-red-900 dark:bg-red-950/50 dark:text-red-200",
+          changed &&
+            side === "new" &&
+            "bg-green-50 text-green-900 dark:bg-green-950/50 dark:text-green-200"
+        )}
+      >
+        {line?.text}
+      </td>
+    </>
+  );
+};
+
+export const DiffView: React.FC<DiffViewProps> = ({ diff, className }) => {
+  return (
+    <div className={cn("border rounded overflow-x-auto", className)}>
+      <div className="px-3 py-1.5 text-xs font-mono border-b bg-gray-50 dark:bg-gray-900">
+        {diff.file_path}
+        {diff.source === "input" && (
+          <span className="ml-2 text-gray-500">
+            (line numbers relative to the edited text)
+          </span>
+        )}
+      </div>
+      <table className="w-full text-xs font-mono border-collapse">
+        {diff.hunks.map((hunk, hunkIndex) => (
+          <tbody key={hunkIndex}>
+            <tr className="bg-blue-50 text-blue-700 dark:bg-blue-950/50 dark:text-blue-300">
+              <td colSpan={4} className="px-2 py-0.5">
+                @@ -{hunk.old_start},{hunk.old_lines} +{hunk.new_start},
+                {hunk.new_lines} @@
+              </td>
+            </tr>
+            {toRows(hunk).map((row, rowIndex) => (
+              <tr key={rowIndex}>
+                <Cell line={row.left} side="old" />
+                <Cell line={row.right} side="new" />
+              </tr>
+            ))}
+          </tbody>
+        ))}
+      </table>
+    </div>
+  );
+};
@@ -16,8 +16,10 @@ import {
 } from "@/components/ui/collapsible";
 import { CodeBlock } from "@/components/CodeBlock";
 import { TodoList } from "@/components/TodoList";
+import { DiffView } from "@/components/DiffView";
 import ReactMarkdown from "react-markdown";
-import type { ChatMessage, ContentBlock } from "@/types/chat";
+import type { ChatMessage, ContentBlock, ImageRef } from "@/types/chat";
+import { tauriApi } from "@/lib/tauri";
 import { processBackspaces } from "@/utils/textProcessing";
 import remarkGfm from "remark-gfm";
 
@@ -64,9 +66,7 @@ interface ContentBlockRendererProps {
 const ContentBlockRenderer: React.FC<ContentBlockRendererProps> = ({
   block,
 }) => {
-  // Handle the case where block_type might be undefined or empty
-  // Also check for 'type' field as data might come with either field name
-  const blockType = block.block_type || (block as any).type || "unknown";
+  const blockType = block.type || "unknown";
 
   switch (blockType) {
     case "text":
@@ -81,6 +81,15 @@ const ContentBlockRenderer: React.FC<ContentBlockRendererProps> = ({
     case "thinking":
       return <ThinkingBlock block={block} />;
 
+    case "image":
+      return block.image ? (
+        <ImageThumbnail image={block.image} alt="Attached image" />
+      ) : (
+        <div className="text-sm text-gray-500 italic">
+          Image not available
+        </div>
+      );
+
     default:
       return (
         <div className="p-2 bg-red-50 border border-red-200 rounded text-sm">
@@ -155,13 +164,49 @@ const MessageText: React.FC<MessageTextProps> = ({ content }) => {
   );
 };
 
+const formatBytes = (bytes: number): string => {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+};
+
+// Dimensions come with the reference, so the thumbnail keeps its space while loading
+const ImageThumbnail: React.FC<{ image: ImageRef; alt: string }> = ({
+  image,
+  alt,
+}) => (
+  <img
+    src={tauriApi.imageUrl(image)}
+    alt={alt}
+    title={`${image.media_type}${
+      image.width && image.height ? `, ${image.width}×${image.height}` : ""
+    }, ${formatBytes(image.byte_size)}`}
+    width={image.width ?? undefined}
+    height={image.height ?? undefined}
+    loading="lazy"
+    className="max-h-80 max-w-full h-auto w-auto rounded border"
+  />
+);
+
+const ToolImages: React.FC<{ images?: ImageRef[] }> = ({ images }) => {
+  if (!images?.length) return null;
+
+  return (
+    <div className="flex flex-wrap gap-2 mt-2">
+      {images.map((image) => (
+        <ImageThumbnail key={image.id} image={image} alt="Tool result image" />
+      ))}
+    </div>
+  );
+};
+
 interface ToolUseBlockProps {
   block: ContentBlock;
 }
 
 const ToolUseBlock: React.FC<ToolUseBlockProps> = ({ block }) => {
   const [isExpanded, setIsExpanded] = useState(false);
-  const hasResult = block.content || block.tool_use_result;
+  const hasResult = block.content || block.tool_use_result || block.images?.length;
 
   return (
     <div className="border border-blue-200 rounded-lg bg-blue-50 dark:bg-blue-950/50 dark:border-blue-800">
@@ -190,14 +235,21 @@ const ToolUseBlock: React.FC<ToolUseBlockProps> = ({ block }) => {
 
         <CollapsibleContent>
           <div className="px-3 pb-3 space-y-3">
-            {block.input && (
+            {block.diff ? (
               <div>
-                <div className="text-sm font-medium mb-1">Input:</div>
-                <CodeBlock
-                  language="json"
-                  code={JSON.stringify(block.input, null, 2)}
-                />
+                <div className="text-sm font-medium mb-1">Changes:</div>
+                <DiffView diff={block.diff} />
               </div>
+            ) : (
+              block.input && (
+                <div>
+                  <div className="text-sm font-medium mb-1">Input:</div>
+                  <CodeBlock
+                    language="json"
+                    code={JSON.stringify(block.input, null, 2)}
+                  />
+                </div>
+              )
             )}
 
             {hasResult && (
@@ -245,11 +297,12 @@ const ToolUseBlock: React.FC<ToolUseBlockProps> = ({ block }) => {
                     )
                   ) : block.content ? (
                     <MessageText content={block.content} isToolResult={true} />
-                  ) : (
+                  ) : block.images?.length ? null : (
                     <div className="text-gray-500 italic">
                       No result content
                     </div>
                   )}
+                  <ToolImages images={block.images} />
                 </div>
               </div>
             )}
@@ -376,9 +429,12 @@ const ToolResultBlock: React.FC<ToolResultBlockProps> = ({ block }) => {
                   {showDebugInfo ? "Hide" : "Show"} debug info
                 </Button>
               </div>
-            ) : block.content ? (
+            ) : block.content || block.images?.length ? (
               <div className="bg-white dark:bg-gray-900 border rounded p-3 font-mono text-sm">
-                <MessageText content={block.content} isToolResult={true} />
+                {block.content && (
+                  <MessageText content={block.content} isToolResult={true} />
+                )}
+                <ToolImages images={block.images} />
               </div>
             ) : (
               <div className="text-gray-500 italic">No content</div>
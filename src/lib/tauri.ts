@@ -1,5 +1,5 @@
-import { invoke } from '@tauri-apps/api/core';
-import type { ChatMessage, ProjectFolder, SearchResult } from '@/types/chat';
+import { convertFileSrc, invoke } from '@tauri-apps/api/core';
+import type { ArchiveRequest, ArchiveSummary, ChatMessage, ConversationTree, DailyCost, DataRoot, FileHistoryEntry, ImageRef, ParseDiagnostics, ExportFormat, PriceTable, ProjectFolder, SearchMode, SearchPage, SearchResult, SessionFile, SessionTail, ToolStats, UsageGroupBy, UsageRange, UsageStats } from '@/types/chat';
 
 export const tauriApi = {
   async getAllProjects(): Promise<ProjectFolder[]> {
@@ -10,8 +10,96 @@ export const tauriApi = {
     return await invoke('get_chat_messages', { sessionId });
   },
 
-  async searchChats(query: string): Promise<SearchResult[]> {
-    return await invoke('search_chats', { query });
+  // Messages appended since offset; pair with "session-file-changed" events to follow a live session
+  async readSessionTail(sessionId: string, offset: number): Promise<SessionTail> {
+    return await invoke('read_session_tail', { sessionId, offset });
+  },
+
+  async getConversationTree(sessionId: string): Promise<ConversationTree> {
+    return await invoke('get_conversation_tree', { sessionId });
+  },
+
+  // Messages from the root to leafUuid, or along the active branch without one
+  async getBranchMessages(sessionId: string, leafUuid?: string): Promise<ChatMessage[]> {
+    return await invoke('get_branch_messages', { sessionId, leafUuid });
+  },
+
+  // Resolves to the rendered document
+  async exportSession(sessionId: string, format?: ExportFormat): Promise<string> {
+    return await invoke('export_session', { sessionId, format });
+  },
+
+  // Writes a zip with the raw JSONL, a rendered copy and a manifest of each selected session
+  async exportArchive(request: ArchiveRequest, destination: string): Promise<ArchiveSummary> {
+    return await invoke('export_archive', { request, destination });
+  },
+
+  async searchChats(query: string, mode?: SearchMode): Promise<SearchResult[]> {
+    return await invoke('search_chats', { query, mode });
+  },
+
+  async searchChatsPage(query: string, mode?: SearchMode, cursor?: string, limit?: number): Promise<SearchPage> {
+    return await invoke('search_chats_page', { query, mode, cursor, limit });
+  },
+
+  // Results arrive as "search-results" events; resolves to false if cancelled
+  async searchChatsStream(searchId: number, query: string, mode?: SearchMode): Promise<boolean> {
+    return await invoke('search_chats_stream', { searchId, query, mode });
+  },
+
+  async cancelSearch(searchId: number): Promise<void> {
+    return await invoke('cancel_search', { searchId });
+  },
+
+  // Pass a ProjectFolder.path to restrict the totals to one project
+  async getDailyCosts(projectPath?: string): Promise<DailyCost[]> {
+    return await invoke('get_daily_costs', { projectPath });
+  },
+
+  async getUsageStats(groupBy: UsageGroupBy, range?: UsageRange): Promise<UsageStats[]> {
+    return await invoke('get_usage_stats', { range, groupBy });
+  },
+
+  // Pass a ProjectFolder.path to restrict the statistics to one project
+  async getToolStats(projectPath?: string): Promise<ToolStats[]> {
+    return await invoke('get_tool_stats', { projectPath });
+  },
+
+  // Files the session read, edited, wrote or created, sorted by path
+  async getSessionFiles(sessionId: string): Promise<SessionFile[]> {
+    return await invoke('get_session_files', { sessionId });
+  },
+
+  // Every session, across all projects, that read or changed the file, most recent first
+  async getFileHistory(path: string): Promise<FileHistoryEntry[]> {
+    return await invoke('get_file_history', { path });
+  },
+
+  // Session files with skipped lines, optionally for one ProjectFolder.path only
+  async getParseDiagnostics(projectPath?: string): Promise<ParseDiagnostics[]> {
+    return await invoke('get_parse_diagnostics', { projectPath });
+  },
+
+  async getPriceTable(): Promise<PriceTable> {
+    return await invoke('get_price_table');
+  },
+
+  async setPriceTable(table: PriceTable): Promise<void> {
+    return await invoke('set_price_table', { table });
+  },
+
+  async getDataRoots(): Promise<DataRoot[]> {
+    return await invoke('get_data_roots');
+  },
+
+  // Each root is a Claude config directory or a projects directory; an empty list restores the default
+  async setDataRoots(roots: string[]): Promise<DataRoot[]> {
+    return await invoke('set_data_roots', { roots });
+  },
+
+  // URL of a cached session image; images are not sent inline over IPC
+  imageUrl(image: ImageRef): string {
+    return convertFileSrc(image.id, 'chatimage');
   },
 
   async getSessionFilePath(sessionId: string): Promise<string> {
@@ -5,45 +5,328 @@ export interface ChatSession {
   project_path: string;
   message_count: number;
   last_updated: string;
+  usage: TokenUsage;
+  cost_usd: number;
 }
 
 export interface ChatMessage {
   uuid: string;
   parent_uuid: string | null;
   timestamp: string;
-  message_type: string; // "user" or "assistant"
+  message_type: string; // "user", "assistant" or "system"
   content: MessageContent;
   tool_use_id: string | null;
   cwd: string | null;
   version: string | null;
   model?: string; // Model name for assistant messages (e.g., "claude-sonnet-4-20250514")
+  is_sidechain?: boolean; // Written by a sub-agent rather than the main conversation
+  usage?: TokenUsage | null; // Assistant messages only
+  cost_usd?: number | null;
+  kind?: MessageKind;
+  images?: ImageRef[]; // Images attached to the message, e.g. pasted screenshots
+}
+
+// What a message in the timeline is, beyond who wrote it
+export type MessageKind =
+  | { type: 'conversation' }
+  | { type: 'meta' } // Context Claude Code added on the user's behalf
+  | { type: 'command'; name: string; args: string } // A slash command such as /compact
+  | { type: 'command_output'; output: string }
+  | { type: 'compact_summary' } // Summary of the history that /compact replaced
+  | { type: 'compact_boundary'; trigger: string | null; pre_tokens: number | null }
+  | { type: 'hook'; event: string | null; tool_use_id: string | null; level: string | null }
+  | { type: 'api_error'; retry_attempt: number | null; max_retries: number | null; retry_in_ms: number | null }
+  | { type: 'system'; subtype: string | null; level: string | null };
+
+export interface TokenUsage {
+  input_tokens: number;
+  output_tokens: number;
+  cache_creation_input_tokens: number;
+  cache_read_input_tokens: number;
+}
+
+export type ExportFormat = 'markdown' | 'html';
+
+// A line of a session file that was skipped because it couldn't be parsed
+export interface ParseIssue {
+  line: number; // 1-based
+  error: string;
+  excerpt: string;
+}
+
+// Session file with skipped lines; issues lists only the first of issue_count
+export interface ParseDiagnostics {
+  session_id: string | null;
+  project_path: string;
+  file_path: string;
+  issue_count: number;
+  issues: ParseIssue[];
+}
+
+// A directory sessions are read from; unavailable when it doesn't exist
+export interface DataRoot {
+  path: string;
+  projects_path: string;
+  available: boolean;
+}
+
+// Sessions to put in an export archive, all projects when project_path is unset
+export interface ArchiveRequest {
+  project_path?: string;
+  range?: UsageRange;
+  format?: ExportFormat;
+}
+
+export interface ArchiveSummary {
+  path: string;
+  sessions: number;
+  bytes: number;
+}
+
+// Payload of the "session-file-changed" event
+export interface SessionFileEvent {
+  kind: 'created' | 'grew' | 'truncated' | 'removed';
+  session_id: string;
+  project_path: string;
+  file_path: string;
+  size: number;
+  previous_size: number; // Offset the appended bytes start at when the file grew
+}
+
+export interface SessionTail {
+  // The first message may continue the last one from the previous read (same message id after '#')
+  messages: ChatMessage[];
+  next_offset: number;
+  reset: boolean; // The file was rewritten and read again from the start
+}
+
+// Inclusive YYYY-MM-DD bounds in local time
+export interface UsageRange {
+  from?: string;
+  to?: string;
+}
+
+export type UsageGroupBy = 'day' | 'week' | 'project' | 'model';
+
+export interface UsageStats {
+  key: string; // Day, week start (Monday), project path or model ("unknown" before a first reply) depending on the grouping
+  sessions: number;
+  user_messages: number; // Prompts typed by the user, tool results excluded
+  assistant_messages: number;
+  tool_calls: number;
+  usage: TokenUsage;
+  cost_usd: number;
+  active_ms: number; // Time between messages, ignoring pauses over five minutes
+}
+
+export interface ToolStats {
+  tool_name: string;
+  calls: number;
+  errors: number; // Calls whose result had is_error set
+  error_rate: number;
+  median_output_bytes: number | null;
+  top_inputs: InputCount[]; // E.g. Bash commands or edited files, most common first
+}
+
+export interface InputCount {
+  input: string;
+  count: number;
+}
+
+// A file a session read or changed through its tool calls
+export interface SessionFile {
+  path: string;
+  reads: number;
+  edits: number; // Edit, MultiEdit and NotebookEdit calls
+  writes: number;
+  globs: number; // Glob searches in this directory
+  created: boolean; // Written by the session when it didn't exist yet
+  first_touched: string;
+  last_touched: string;
+  final_edit: FileEdit | null; // The last call that changed the file
+}
+
+export interface FileEdit {
+  tool_name: string;
+  message_uuid: string;
+  timestamp: string;
+  diff: FileDiff | null; // Null for notebook edits
+}
+
+// A session that touched the file passed to getFileHistory
+export interface FileHistoryEntry {
+  session: ChatSession;
+  file: SessionFile;
+}
+
+export interface DailyCost {
+  date: string; // YYYY-MM-DD in local time
+  usage: TokenUsage;
+  cost_usd: number;
+}
+
+// Dollar prices per million tokens for models whose name contains `pattern`
+export interface ModelPrice {
+  pattern: string;
+  input: number;
+  output: number;
+  cache_write: number;
+  cache_read: number;
+}
+
+// The first matching entry wins
+export interface PriceTable {
+  models: ModelPrice[];
 }
 
 export type MessageContent = string | ContentBlock[];
 
+// An image decoded into the backend's image cache, served over the chatimage protocol
+export interface ImageRef {
+  id: string; // Content hash and extension
+  media_type: string;
+  width: number | null; // Null when the image header couldn't be read
+  height: number | null;
+  byte_size: number;
+}
+
+// The change a file-editing tool call makes, as unified diff hunks
+export interface FileDiff {
+  file_path: string;
+  // "patch": from the tool's structuredPatch, numbered by file line
+  // "input": computed from the call's input, numbered from the start of the edited text
+  source: 'patch' | 'input';
+  hunks: DiffHunk[];
+}
+
+export interface DiffHunk {
+  old_start: number;
+  old_lines: number;
+  new_start: number;
+  new_lines: number;
+  lines: DiffLine[];
+}
+
+export interface DiffLine {
+  kind: 'context' | 'added' | 'removed';
+  old_line: number | null; // Null for added lines
+  new_line: number | null; // Null for removed lines
+  text: string;
+}
+
+// Blocks of types the backend doesn't know are passed through as written
 export interface ContentBlock {
-  block_type?: string; // "text", "tool_use", "tool_result", "thinking"
-  type?: string; // Alternative field name for block_type
+  type: string; // "text", "tool_use", "tool_result", "thinking", "redacted_thinking", "image", "document", "server_tool_use", "web_search_tool_result", ...
   text?: string;
+  id?: string; // Tool call id, on tool_use and server_tool_use
   name?: string; // Tool name
   input?: any;
   tool_use_id?: string;
-  content?: string; // Tool result content
+  content?: any; // Tool result text; a list of results on web_search_tool_result
+  signature?: string | null; // Thinking signature
+  data?: string; // Encrypted redacted_thinking content
+  source?: any; // Image or document source; cached images arrive without their base64 data
+  image?: ImageRef | null; // Set on image blocks once the image is cached
+  title?: string | null; // Document title
   tool_use_result?: any; // For TodoWrite and other structured results
+  is_error?: boolean | null; // Set on tool results, and on tool calls once their result is merged
+  images?: ImageRef[]; // Images returned by a tool, load them with imageUrl
+  diff?: FileDiff | null; // The change made by an Edit, MultiEdit or Write call
   thinking?: string; // For thinking blocks
+  sidechain?: ChatMessage[] | null; // Sub-agent transcript of a Task tool call
+}
+
+// Messages of a session linked by parent_uuid, listed in file order
+export interface ConversationTree {
+  session_id: string;
+  nodes: ConversationNode[];
+  roots: string[];
+  leaves: string[];
+  active_leaf: string | null;
+}
+
+export interface ConversationNode {
+  uuid: string; // Line uuid, without the message id suffix of ChatMessage.uuid
+  parent_uuid: string | null;
+  children: string[]; // More than one child means the conversation branched here
+  message: ChatMessage;
+  is_active: boolean; // On the path to the active leaf
+  summary: string | null;
 }
 
 export interface ProjectFolder {
   name: string;
   path: string;
   chat_sessions: ChatSession[];
+  usage: TokenUsage;
+  cost_usd: number;
 }
 
+// One result per message; snippet/match_type/highlight describe the best match
+// and `matches` lists every field of the message that matched
 export interface SearchResult {
   session_id: string;
   message_uuid: string;
   snippet: string;
-  match_type: string; // "content", "tool_name", "tool_result"
+  match_type: string; // "content", "thinking", "tool_name", "tool_input", "tool_result", "tool_structured_result"
+  highlight: SnippetHighlight | null;
+  project_path: string;
+  session_title: string | null;
+  timestamp: string;
+  role: string;
+  model: string | null;
+  tool_name: string | null;
+  matches: SearchMatch[];
+}
+
+export interface SearchMatch {
+  match_type: string;
+  snippet: string;
+  highlight: SnippetHighlight | null;
+  tool_name: string | null;
+}
+
+// Offsets of the match inside the snippet, in UTF-8 bytes and in characters
+export interface SnippetHighlight {
+  byte_start: number;
+  byte_end: number;
+  char_start: number;
+  char_end: number;
+}
+
+export interface SearchPage {
+  results: SearchResult[];
+  next_cursor: string | null; // Pass back to fetch the next page, null on the last one
+}
+
+// Payload of the "search-results" event emitted while searchChatsStream runs
+export interface SearchBatch {
+  search_id: number;
+  results: SearchResult[];
+}
+
+export type SearchMode = 'text' | 'regex' | 'fuzzy';
+
+export type SearchError =
+  | { kind: 'invalid_regex'; pattern: string; message: string }
+  | { kind: 'invalid_query'; message: string }
+  | { kind: 'internal'; message: string };
+
+// Error returned by every command except the search ones
+export type CommandErrorKind =
+  | 'session_not_found'
+  | 'data_root_missing'
+  | 'file_not_found'
+  | 'permission_denied'
+  | 'corrupt_data'
+  | 'invalid_input'
+  | 'internal';
+
+export interface CommandError {
+  kind: CommandErrorKind;
+  message: string;
+  path: string | null;
+  line: number | null; // 1-based line in path
 }
 
 // Utility function for processing backspace characters
@@ -104,7 +387,7 @@ export const getToolCalls = (message: ChatMessage): ContentBlock[] => {
   }
   
   if (Array.isArray(message.content)) {
-    return message.content.filter(block => (block.block_type || block.type) === 'tool_use');
+    return message.content.filter(block => block.type === 'tool_use');
   }
   
   return [];